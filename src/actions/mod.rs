@@ -1,49 +1,194 @@
 //! Actions for anyframe-rs
 //!
 //! Actions perform operations on selected items, such as executing, inserting, or putting them.
+//!
+//! Actions do not touch the shell themselves. They describe the edit to apply to the
+//! calling shell's line editor as a [`ZleOutput`], which the binary prints as zsh code
+//! that the widget functions of `anyframe.plugin.zsh` evaluate.
 
 use crate::{error, Result};
-use std::process::Command;
+use std::fmt;
+use std::path::PathBuf;
 
 /// Trait for actions
 pub trait Action {
-    /// Perform the action on the selected item
-    fn perform(&self, item: &str) -> Result<()>;
+    /// Perform the action on the selected items
+    ///
+    /// How several items are combined is up to the action: see the documentation
+    /// of each implementation.
+    fn perform(&self, items: &[String]) -> Result<ZleOutput>;
 
     /// Get the name of the action
     fn name(&self) -> &str;
 }
 
+impl<T: Action + ?Sized> Action for Box<T> {
+    fn perform(&self, items: &[String]) -> Result<ZleOutput> {
+        (**self).perform(items)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
+
+/// Names of the actions accepted by [`from_name`]
+pub const ACTION_NAMES: &[&str] = &["execute", "insert", "put", "cd"];
+
+/// Create an action from its name
+///
+/// `cd` creates a [`ChangeDirectory`] action, the other names the action of the same name.
+pub fn from_name(name: &str) -> Result<Box<dyn Action>> {
+    Ok(match name {
+        "execute" => Box::new(Execute),
+        "insert" => Box::new(Insert),
+        "put" => Box::new(Put::new(false)),
+        "cd" | "change-directory" => Box::new(ChangeDirectory),
+        _ => {
+            return Err(error::AnyframeError::ActionError(format!(
+                "Unknown action '{}' (available: {})",
+                name,
+                ACTION_NAMES.join(", ")
+            )))
+        }
+    })
+}
+
+/// Changes to apply to the calling shell's ZLE state
+///
+/// The output is rendered as a sequence of zsh statements, one per line, in this order:
+///
+/// ```zsh
+/// builtin cd -- '/path/to/dir'
+/// BUFFER='git checkout main'
+/// CURSOR=17
+/// LBUFFER+='inserted text'
+/// zle accept-line
+/// zle -M 'message'
+/// ```
+///
+/// Every value is single-quoted, so the output is safe to `eval`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZleOutput {
+    /// Replacement for the whole command line buffer
+    pub buffer: Option<String>,
+    /// Cursor position in characters
+    pub cursor: Option<usize>,
+    /// Text inserted at the cursor position
+    pub insert: Option<String>,
+    /// Directory the shell should change into
+    pub directory: Option<PathBuf>,
+    /// Whether the command line should be accepted (executed)
+    pub accept_line: bool,
+    /// Message shown below the command line, such as the outcome of the widget
+    pub message: Option<String>,
+}
+
+impl ZleOutput {
+    /// Create an output that leaves the shell untouched
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the buffer and move the cursor to its end
+    #[must_use]
+    pub fn buffer(mut self, buffer: impl Into<String>) -> Self {
+        let buffer = buffer.into();
+        self.cursor = Some(buffer.chars().count());
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Insert text at the cursor position
+    #[must_use]
+    pub fn insert(mut self, text: impl Into<String>) -> Self {
+        self.insert = Some(text.into());
+        self
+    }
+
+    /// Change the shell's current directory
+    #[must_use]
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Accept the command line after applying the other changes
+    #[must_use]
+    pub fn accept_line(mut self) -> Self {
+        self.accept_line = true;
+        self
+    }
+
+    /// Show a message below the command line
+    #[must_use]
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+impl fmt::Display for ZleOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(directory) = &self.directory {
+            writeln!(
+                f,
+                "builtin cd -- {}",
+                shell_quote(&directory.to_string_lossy())
+            )?;
+        }
+        if let Some(buffer) = &self.buffer {
+            writeln!(f, "BUFFER={}", shell_quote(buffer))?;
+        }
+        if let Some(cursor) = self.cursor {
+            writeln!(f, "CURSOR={}", cursor)?;
+        }
+        if let Some(insert) = &self.insert {
+            writeln!(f, "LBUFFER+={}", shell_quote(insert))?;
+        }
+        if self.accept_line {
+            writeln!(f, "zle accept-line")?;
+        } else if self.directory.is_some() {
+            writeln!(f, "zle reset-prompt")?;
+        }
+        if let Some(message) = &self.message {
+            writeln!(f, "zle -M {}", shell_quote(message))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote a string for zsh using single quotes
+///
+/// Strings made only of characters that are never special to the shell are returned
+/// unchanged, like the `(q-)` parameter flag in zsh. Embedded single quotes are
+/// written as `'\''`.
+#[must_use]
+pub fn shell_quote(s: &str) -> String {
+    let is_plain = !s.is_empty()
+        && s.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '_' | '-' | '.' | '/' | ',' | ':' | '@' | '%' | '+')
+        });
+
+    if is_plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
 /// Execute action
 ///
-/// Executes the selected command in zsh.
+/// Puts the selected command into the buffer and accepts the line.
+/// Several commands are put on separate lines and run one after another.
 /// Similar to the original anyframe-action-execute function.
 pub struct Execute;
 
 impl Action for Execute {
-    fn perform(&self, item: &str) -> Result<()> {
-        // Execute the selected command in zsh
-        // We use BUFFER and accept-line to execute the command in zsh
-        // This is similar to how the original anyframe-action-execute function works
-        let execute_output = Command::new("zsh")
-            .arg("-c")
-            .arg(format!(
-                "BUFFER=\"{}\"; zle accept-line 2>/dev/null || eval \"$BUFFER\"",
-                item.replace('"', "\\\"").replace('$', "\\$")
-            ))
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::ActionError(format!("Failed to execute command: {}", e))
-            })?;
-
-        if !execute_output.status.success() {
-            return Err(error::AnyframeError::ActionError(format!(
-                "Execute command failed: {}",
-                String::from_utf8_lossy(&execute_output.stderr)
-            )));
-        }
-
-        Ok(())
+    fn perform(&self, items: &[String]) -> Result<ZleOutput> {
+        Ok(ZleOutput::new().buffer(items.join("\n")).accept_line())
     }
 
     fn name(&self) -> &'static str {
@@ -52,30 +197,15 @@ impl Action for Execute {
 }
 
 /// Insert action
+///
+/// Inserts the selected item at the cursor position.
+/// Several items are inserted separated by spaces.
+/// Similar to the original anyframe-action-insert function.
 pub struct Insert;
 
 impl Action for Insert {
-    fn perform(&self, item: &str) -> Result<()> {
-        // Implementation to insert the selected item at the cursor position in zsh
-        let insert_output = Command::new("zsh")
-            .arg("-c")
-            .arg(format!("print -z \"{}\"", item))
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::ActionError(format!(
-                    "Failed to execute insert command: {}",
-                    e
-                ))
-            })?;
-
-        if !insert_output.status.success() {
-            return Err(error::AnyframeError::ActionError(format!(
-                "Insert command failed: {}",
-                String::from_utf8_lossy(&insert_output.stderr)
-            )));
-        }
-
-        Ok(())
+    fn perform(&self, items: &[String]) -> Result<ZleOutput> {
+        Ok(ZleOutput::new().insert(items.join(" ")))
     }
 
     fn name(&self) -> &'static str {
@@ -86,6 +216,7 @@ impl Action for Insert {
 /// Put action
 ///
 /// Puts the selected item into the command line buffer.
+/// Several items are put on separate lines.
 /// Similar to the original anyframe-action-put function.
 pub struct Put {
     quote_item: bool,
@@ -100,43 +231,15 @@ impl Put {
 }
 
 impl Action for Put {
-    fn perform(&self, item: &str) -> Result<()> {
-        // Implementation to put the selected item into the command line buffer
-        let quoted_item = if self.quote_item {
-            // Quote special characters with backslashes
-            // This is similar to the (q) parameter in zsh
-            format!(
-                "\\\"{}\\\"",
-                item.replace('\\', "\\\\")
-                    .replace('"', "\\\"")
-                    .replace('$', "\\$")
-            )
+    fn perform(&self, items: &[String]) -> Result<ZleOutput> {
+        // Quote the items like the (q) parameter flag in zsh when requested
+        let items: Vec<String> = if self.quote_item {
+            items.iter().map(|item| shell_quote(item)).collect()
         } else {
-            item.to_string()
+            items.to_vec()
         };
 
-        let put_output = Command::new("zsh")
-            .arg("-c")
-            .arg(format!(
-                "BUFFER=\"{}\"; CURSOR=$#BUFFER; zle -R -c 2>/dev/null || print -z -f '%s' \"$BUFFER\"",
-                quoted_item
-            ))
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::ActionError(format!(
-                    "Failed to execute put command: {}",
-                    e
-                ))
-            })?;
-
-        if !put_output.status.success() {
-            return Err(error::AnyframeError::ActionError(format!(
-                "Put command failed: {}",
-                String::from_utf8_lossy(&put_output.stderr)
-            )));
-        }
-
-        Ok(())
+        Ok(ZleOutput::new().buffer(items.join("\n")))
     }
 
     fn name(&self) -> &'static str {
@@ -145,28 +248,30 @@ impl Action for Put {
 }
 
 /// Change directory action
+///
+/// Changes the calling shell's current directory to the selected item.
+/// A leading `~` is expanded to `$HOME`. Only the first of several items is used.
 pub struct ChangeDirectory;
 
 impl Action for ChangeDirectory {
-    fn perform(&self, item: &str) -> Result<()> {
-        // Change directory to the selected repository
-        // This will need to interact with zsh to change the directory
-        let cd_output = Command::new("zsh")
-            .arg("-c")
-            .arg(format!("cd {}", item))
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::ActionError(format!("Failed to execute cd command: {}", e))
-            })?;
-
-        if !cd_output.status.success() {
-            return Err(error::AnyframeError::ActionError(format!(
-                "cd command failed: {}",
-                String::from_utf8_lossy(&cd_output.stderr)
-            )));
-        }
+    fn perform(&self, items: &[String]) -> Result<ZleOutput> {
+        let Some(item) = items.first() else {
+            return Ok(ZleOutput::new());
+        };
 
-        Ok(())
+        let directory = match (item.strip_prefix('~'), std::env::var_os("HOME")) {
+            (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                let mut path = PathBuf::from(home);
+                let rest = rest.trim_start_matches('/');
+                if !rest.is_empty() {
+                    path.push(rest);
+                }
+                path
+            }
+            _ => PathBuf::from(item.as_str()),
+        };
+
+        Ok(ZleOutput::new().directory(directory))
     }
 
     fn name(&self) -> &'static str {
@@ -174,5 +279,81 @@ impl Action for ChangeDirectory {
     }
 }
 
-// The Put action is already defined above
-// Similar implementations for other actions to be added
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_leaves_plain_words_unchanged() {
+        assert_eq!(shell_quote("feature/login-2.0"), "feature/login-2.0");
+        assert_eq!(shell_quote("user@host:50%"), "user@host:50%");
+    }
+
+    #[test]
+    fn shell_quote_quotes_special_characters() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("'"), r"''\'''");
+        assert_eq!(shell_quote("a\nb"), "'a\nb'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn renders_statements_in_order() {
+        let output = ZleOutput::new()
+            .message("done")
+            .accept_line()
+            .directory("/tmp/my dir")
+            .buffer("git checkout main");
+        assert_eq!(
+            output.to_string(),
+            "builtin cd -- '/tmp/my dir'\n\
+             BUFFER='git checkout main'\n\
+             CURSOR=17\n\
+             zle accept-line\n\
+             zle -M done\n"
+        );
+    }
+
+    #[test]
+    fn renders_insertions() {
+        assert_eq!(
+            ZleOutput::new().insert("a b").to_string(),
+            "LBUFFER+='a b'\n"
+        );
+        assert_eq!(ZleOutput::new().to_string(), "");
+    }
+
+    #[test]
+    fn counts_the_cursor_in_characters() {
+        let output = ZleOutput::new().buffer("echo 日本語");
+        assert_eq!(output.cursor, Some(8));
+        assert_eq!(output.to_string(), "BUFFER='echo 日本語'\nCURSOR=8\n");
+    }
+
+    #[test]
+    fn change_directory_resets_the_prompt() -> Result<()> {
+        let output = ChangeDirectory.perform(&["/tmp".to_string(), "/usr".to_string()])?;
+        assert_eq!(output.to_string(), "builtin cd -- /tmp\nzle reset-prompt\n");
+        assert_eq!(ChangeDirectory.perform(&[])?, ZleOutput::new());
+        Ok(())
+    }
+
+    #[test]
+    fn change_directory_expands_the_home_directory() -> Result<()> {
+        let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+            return Ok(());
+        };
+        let directory = |item: &str| {
+            ChangeDirectory
+                .perform(&[item.to_string()])
+                .map(|output| output.directory)
+        };
+        assert_eq!(directory("~")?, Some(home.clone()));
+        assert_eq!(directory("~/")?, Some(home.clone()));
+        assert_eq!(directory("~/src")?, Some(home.join("src")));
+        assert_eq!(directory("~user/src")?, Some(PathBuf::from("~user/src")));
+        Ok(())
+    }
+}
@@ -0,0 +1,963 @@
+//! Configuration file for anyframe-rs
+//!
+//! The configuration is a TOML file read from `$XDG_CONFIG_HOME/anyframe-rs/config.toml`
+//! (`~/.config/anyframe-rs/config.toml` by default). Another file can be given with
+//! `--config` or the `ANYFRAME_CONFIG` environment variable.
+//!
+//! ```toml
+//! [selector]
+//! priority = ["fzf", "peco", "native"]
+//! name = "fzf"
+//! command = "fzf --exact"
+//! prompt = "> "
+//! height = "40%"
+//! layout = "reverse"
+//! # Inside tmux, show the selector in a popup
+//! popup = { width = "80%", height = "60%" }
+//!
+//! # Any other picker, usable by name like the built-in selectors
+//! [selectors.mypicker]
+//! command = "mypicker --prompt={prompt} {multi}"
+//! multi-flag = "--multi"
+//! cancel-exit-code = 130
+//!
+//! [widgets.checkout-git-branch]
+//! selector = "peco"
+//! prompt = "branch> "
+//! header = "Enter: checkout"
+//! all = true
+//!
+//! # Alternate actions, run when the selection is accepted with the key
+//! [widgets.checkout-git-branch.keys]
+//! ctrl-o = { action = "insert" }
+//! ctrl-d = { action = "execute", template = "git branch -d {}" }
+//!
+//! [sources.history]
+//! shell = "zsh"
+//! histfile = "~/.zsh_history"
+//!
+//! # Custom widget, run with `anyframe-rs run docker-container`
+//! [widgets.docker-container]
+//! source = "command:docker ps --format 'table {{.ID}} {{.Names}}'"
+//! header-lines = 1
+//! field = 1
+//! preview = "docker inspect {}"
+//! action = "insert"
+//!
+//! [bindings]
+//! "^xr" = "execute-history"
+//! "^xd" = "docker-container"
+//! ```
+
+use crate::{
+    actions::{self, Action},
+    error,
+    selectors::{self, CustomCommand, Layout, Popup, SelectOptions, SelectorRegistry},
+    sources::{self, history::HistoryBackend, CommandOutput, Source},
+    widgets::{KeyAction, Signal},
+    Result,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Names of the built-in widgets, as used by the subcommands
+pub const BUILTIN_WIDGETS: &[&str] = &[
+    "execute-history",
+    "put-history",
+    "cdr",
+    "cd-directory",
+    "cd-ghq-repository",
+    "checkout-git-branch",
+    "insert-git-branch",
+    "insert-filename",
+    "git-add",
+    "kill",
+];
+
+/// Widget options that only apply to some widgets, with the widgets supporting them
+const WIDGET_FLAGS: &[(&str, &[&str])] = &[
+    (
+        "include-current",
+        &["checkout-git-branch", "insert-git-branch"],
+    ),
+    ("remote", &["checkout-git-branch", "insert-git-branch"]),
+    ("all", &["checkout-git-branch", "insert-git-branch"]),
+    ("pattern", &["git-add", "kill"]),
+    ("all-users", &["kill"]),
+    ("tree", &["kill"]),
+    ("name", &["kill"]),
+    ("signal", &["kill"]),
+    ("no-confirm", &["kill"]),
+    ("escalate", &["kill"]),
+    ("max-depth", &["cd-directory"]),
+    ("hidden", &["cd-directory", "insert-filename"]),
+    ("follow-symlinks", &["cd-directory"]),
+    ("no-ignore", &["cd-directory", "insert-filename"]),
+    ("git-root", &["insert-filename"]),
+    ("git-ls-files", &["insert-filename"]),
+];
+
+/// Widget options that only apply to custom widgets
+const CUSTOM_WIDGET_OPTIONS: &[&str] = &[
+    "source",
+    "action",
+    "field",
+    "delimiter",
+    "header-lines",
+    "preview",
+    "template",
+    "multi",
+];
+
+/// Configuration of anyframe-rs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Default selector
+    pub selector: SelectorConfig,
+    /// Custom selector commands, by selector name
+    pub selectors: BTreeMap<String, CustomSelectorConfig>,
+    /// Options of each widget, by widget name
+    pub widgets: BTreeMap<String, WidgetConfig>,
+    /// Options of the sources
+    pub sources: SourcesConfig,
+    /// Key bindings, from key sequence to widget name
+    pub bindings: BTreeMap<String, String>,
+}
+
+/// Selector settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SelectorConfig {
+    /// Selectors tried in order when no name or command is set (default selector only)
+    pub priority: Option<Vec<String>>,
+    /// Selector name, such as `fzf`
+    pub name: Option<String>,
+    /// Selector command line with arguments, such as `fzf --exact`
+    pub command: Option<String>,
+    /// Prompt shown by the selector
+    pub prompt: Option<String>,
+    /// Height of the selector, such as `40%`
+    pub height: Option<String>,
+    /// Arrangement of the prompt and the candidates (`default` or `reverse`)
+    pub layout: Option<Layout>,
+    /// Whether to sort the candidates by score
+    pub sort: Option<bool>,
+    /// Show the selector in a tmux popup with this size and position (inside tmux)
+    pub popup: Option<Popup>,
+}
+
+/// Custom selector command, usable by its name like the built-in selectors
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CustomSelectorConfig {
+    /// Command line, whose arguments may contain `{query}`, `{prompt}` and `{multi}`
+    pub command: String,
+    /// Argument replacing `{multi}` in multi-select mode, such as `--multi`
+    pub multi_flag: Option<String>,
+    /// Exit code of the command meaning that the selection was cancelled
+    pub cancel_exit_code: Option<i32>,
+    /// Exit code of the command meaning that nothing matched
+    pub no_match_exit_code: Option<i32>,
+}
+
+impl CustomSelectorConfig {
+    /// Split the command line into the program and the argument templates
+    pub fn command_line(&self) -> Result<(String, Vec<String>)> {
+        let mut words = shell_words::split(&self.command)
+            .map_err(|e| error::AnyframeError::ConfigError(format!("invalid command: {}", e)))?;
+        if words.is_empty() {
+            return Err(error::AnyframeError::ConfigError(
+                "empty command".to_string(),
+            ));
+        }
+        let program = words.remove(0);
+        Ok((program, words))
+    }
+
+    /// Create the selector running a program with argument templates, registered under
+    /// the given name
+    #[must_use]
+    pub fn selector(&self, name: &str, program: String, args: Vec<String>) -> CustomCommand {
+        let mut selector = CustomCommand::new(name, program).with_args(args);
+        if let Some(flag) = &self.multi_flag {
+            selector = selector.multi_flag(flag.as_str());
+        }
+        if let Some(code) = self.cancel_exit_code {
+            selector = selector.cancel_exit_code(code);
+        }
+        if let Some(code) = self.no_match_exit_code {
+            selector = selector.no_match_exit_code(code);
+        }
+        selector
+    }
+}
+
+/// Options of a widget
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WidgetConfig {
+    /// Selector name overriding the default selector
+    pub selector: Option<String>,
+    /// Selector command line overriding the default selector
+    pub selector_command: Option<String>,
+    /// Prompt shown by the selector
+    pub prompt: Option<String>,
+    /// Header shown above the candidates
+    pub header: Option<String>,
+    /// Height of the selector, such as `40%`
+    pub height: Option<String>,
+    /// Arrangement of the prompt and the candidates (`default` or `reverse`)
+    pub layout: Option<Layout>,
+    /// Whether to sort the candidates by score
+    pub sort: Option<bool>,
+    /// Show the selector in a tmux popup with this size and position (inside tmux)
+    pub popup: Option<Popup>,
+    /// Include the current branch (git branch widgets)
+    pub include_current: Option<bool>,
+    /// Show remote branches (git branch widgets)
+    pub remote: Option<bool>,
+    /// Show all branches (git branch widgets)
+    pub all: Option<bool>,
+    /// Only show files whose status starts with this pattern (git-add), or processes
+    /// whose command line matches this regular expression (kill)
+    pub pattern: Option<String>,
+    /// List the processes of all users (kill)
+    pub all_users: Option<bool>,
+    /// Show each process below its parent (kill)
+    pub tree: Option<bool>,
+    /// Only list the processes with this name (kill)
+    pub name: Option<String>,
+    /// Signal to send, such as `TERM` or `KILL`, instead of picking one (kill)
+    pub signal: Option<String>,
+    /// Signal the selected processes without confirmation (kill)
+    pub no_confirm: Option<bool>,
+    /// Send `SIGKILL` to the processes still running this many seconds after a
+    /// terminating signal (kill)
+    pub escalate: Option<u64>,
+    /// Only descend this many levels below the current directory (cd-directory)
+    pub max_depth: Option<usize>,
+    /// Include hidden directories or files (cd-directory, insert-filename)
+    pub hidden: Option<bool>,
+    /// Follow symbolic links to directories (cd-directory)
+    pub follow_symlinks: Option<bool>,
+    /// Do not respect `.gitignore` and `.ignore` files (cd-directory, insert-filename)
+    pub no_ignore: Option<bool>,
+    /// List the files of the whole git repository (insert-filename)
+    pub git_root: Option<bool>,
+    /// List the files with `git ls-files` inside git repositories (insert-filename)
+    pub git_ls_files: Option<bool>,
+    /// Source of a custom widget: a source name or `command:<command line>`
+    pub source: Option<String>,
+    /// Action of a custom widget (`execute`, `insert`, `put` or `cd`)
+    pub action: Option<String>,
+    /// Field of each line used as the value (custom widgets with a command source)
+    pub field: Option<usize>,
+    /// Delimiter of the fields instead of whitespace (custom widgets)
+    pub delimiter: Option<String>,
+    /// Number of lines at the top of the output shown as a header that cannot be
+    /// selected (custom widgets with a command source)
+    pub header_lines: Option<usize>,
+    /// Shell command previewing a candidate, with `{}` standing for its quoted value
+    /// (custom widgets with a command source)
+    pub preview: Option<String>,
+    /// Template receiving the selected values in place of `{}` (custom widgets)
+    pub template: Option<String>,
+    /// Allow selecting several candidates (custom widgets)
+    pub multi: Option<bool>,
+    /// Alternate actions, by the key accepting the selection (e.g. `ctrl-o`)
+    pub keys: BTreeMap<String, KeyConfig>,
+}
+
+/// Alternate action of a widget, run when the selection is accepted with its key
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct KeyConfig {
+    /// Action (`execute`, `insert`, `put` or `cd`)
+    pub action: String,
+    /// Template receiving the selected values in place of `{}`
+    pub template: Option<String>,
+}
+
+impl WidgetConfig {
+    /// Get the selector of this widget, falling back to the default selector
+    ///
+    /// The name and command line are taken together from the first level that sets
+    /// either of them, so that a widget override never mixes with the default. The
+    /// other settings fall back one by one.
+    #[must_use]
+    pub fn selector(&self, default: &SelectorConfig) -> SelectorConfig {
+        let (name, command) = if self.selector.is_some() || self.selector_command.is_some() {
+            (&self.selector, &self.selector_command)
+        } else {
+            (&default.name, &default.command)
+        };
+
+        SelectorConfig {
+            priority: default.priority.clone(),
+            name: name.clone(),
+            command: command.clone(),
+            prompt: self.prompt.clone().or_else(|| default.prompt.clone()),
+            height: self.height.clone().or_else(|| default.height.clone()),
+            layout: self.layout.or(default.layout),
+            sort: self.sort.or(default.sort),
+            popup: self.popup.clone().or_else(|| default.popup.clone()),
+        }
+    }
+
+    /// Get the selector options of this widget, falling back to the default selector
+    #[must_use]
+    pub fn options(&self, default: &SelectorConfig) -> SelectOptions {
+        let selector = self.selector(default);
+        SelectOptions {
+            prompt: selector.prompt,
+            header: self.header.clone(),
+            height: selector.height,
+            layout: selector.layout,
+            sort: selector.sort,
+            popup: selector.popup,
+            ..SelectOptions::default()
+        }
+    }
+
+    /// Create the alternate actions of this widget, by key
+    pub fn key_actions(&self) -> Result<Vec<(String, KeyAction)>> {
+        self.keys
+            .iter()
+            .map(|(key, config)| {
+                if !selectors::is_key_name(key) {
+                    return Err(error::AnyframeError::ConfigError(format!(
+                        "keys.{}: unknown key (expected ctrl-<letter>, alt-<character> or f1 to f12)",
+                        key
+                    )));
+                }
+                let action = actions::from_name(&config.action).map_err(|e| match e {
+                    error::AnyframeError::ActionError(message) => {
+                        error::AnyframeError::ConfigError(format!("keys.{}: {}", key, message))
+                    }
+                    e => e,
+                })?;
+
+                let mut key_action = KeyAction::new(action);
+                if let Some(template) = &config.template {
+                    key_action = key_action.template(template.as_str());
+                }
+                Ok((key.clone(), key_action))
+            })
+            .collect()
+    }
+
+    /// Names of the widget-specific options that are set
+    ///
+    /// The destructuring lists every field, so that a new option cannot be forgotten.
+    fn flags(&self) -> Vec<&'static str> {
+        let Self {
+            selector: _,
+            selector_command: _,
+            prompt: _,
+            header: _,
+            height: _,
+            layout: _,
+            sort: _,
+            popup: _,
+            include_current,
+            remote,
+            all,
+            pattern,
+            all_users,
+            tree,
+            name,
+            signal,
+            no_confirm,
+            escalate,
+            max_depth,
+            hidden,
+            follow_symlinks,
+            no_ignore,
+            git_root,
+            git_ls_files,
+            source,
+            action,
+            field,
+            delimiter,
+            header_lines,
+            preview,
+            template,
+            multi,
+            keys: _,
+        } = self;
+
+        [
+            ("include-current", include_current.is_some()),
+            ("remote", remote.is_some()),
+            ("all", all.is_some()),
+            ("pattern", pattern.is_some()),
+            ("all-users", all_users.is_some()),
+            ("tree", tree.is_some()),
+            ("name", name.is_some()),
+            ("signal", signal.is_some()),
+            ("no-confirm", no_confirm.is_some()),
+            ("escalate", escalate.is_some()),
+            ("max-depth", max_depth.is_some()),
+            ("hidden", hidden.is_some()),
+            ("follow-symlinks", follow_symlinks.is_some()),
+            ("no-ignore", no_ignore.is_some()),
+            ("git-root", git_root.is_some()),
+            ("git-ls-files", git_ls_files.is_some()),
+            ("source", source.is_some()),
+            ("action", action.is_some()),
+            ("field", field.is_some()),
+            ("delimiter", delimiter.is_some()),
+            ("header-lines", header_lines.is_some()),
+            ("preview", preview.is_some()),
+            ("template", template.is_some()),
+            ("multi", multi.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(flag, set)| set.then_some(flag))
+        .collect()
+    }
+
+    /// Create the source of a custom widget
+    pub fn source(&self) -> Result<Box<dyn Source>> {
+        let spec = self.source.as_deref().ok_or_else(|| {
+            error::AnyframeError::ConfigError("custom widget without a source".to_string())
+        })?;
+
+        let command = spec
+            .strip_prefix("command:")
+            .map(str::trim)
+            .filter(|command| !command.is_empty());
+        let Some(command) = command else {
+            if self.field.is_some()
+                || self.delimiter.is_some()
+                || self.header_lines.is_some()
+                || self.preview.is_some()
+            {
+                return Err(error::AnyframeError::ConfigError(
+                    "field, delimiter, header-lines and preview require a command: source"
+                        .to_string(),
+                ));
+            }
+            return sources::from_spec(spec);
+        };
+
+        let mut source = CommandOutput::new(command);
+        if let Some(field) = self.field {
+            if field == 0 {
+                return Err(error::AnyframeError::ConfigError(
+                    "field numbers start at 1".to_string(),
+                ));
+            }
+            source = source.with_field(field);
+        }
+        if let Some(delimiter) = &self.delimiter {
+            source = source.with_delimiter(delimiter.as_str());
+        }
+        if let Some(header_lines) = self.header_lines {
+            source = source.with_header_lines(header_lines);
+        }
+        if let Some(preview) = &self.preview {
+            source = source.with_preview(preview.as_str());
+        }
+        Ok(Box::new(source))
+    }
+
+    /// Create the action of a custom widget
+    pub fn action(&self) -> Result<Box<dyn Action>> {
+        let name = self.action.as_deref().ok_or_else(|| {
+            error::AnyframeError::ConfigError("custom widget without an action".to_string())
+        })?;
+        actions::from_name(name)
+    }
+}
+
+/// Options of the sources
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SourcesConfig {
+    /// Options of the history source
+    pub history: HistoryConfig,
+}
+
+/// Options of the history source
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HistoryConfig {
+    /// Shell whose history is read (`zsh`, `bash` or `fish`)
+    pub shell: Option<String>,
+    /// History file to read instead of the shell's default
+    pub histfile: Option<PathBuf>,
+}
+
+impl HistoryConfig {
+    /// Get the configured history backend
+    pub fn backend(&self) -> Result<Option<HistoryBackend>> {
+        self.shell
+            .as_deref()
+            .map(|shell| {
+                shell.parse().map_err(|_| {
+                    error::AnyframeError::ConfigError(format!(
+                        "sources.history.shell: unknown shell '{}' (expected zsh, bash or fish)",
+                        shell
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Get the configured history file with a leading `~` expanded
+    #[must_use]
+    pub fn histfile(&self) -> Option<PathBuf> {
+        self.histfile.as_deref().map(expand_tilde)
+    }
+}
+
+impl Config {
+    /// Get the default location of the configuration file
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("anyframe-rs").join("config.toml"))
+    }
+
+    /// Load the configuration
+    ///
+    /// An explicitly given file must exist. When no file is given, the default file is
+    /// read if it exists and an empty configuration is used otherwise.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| error::AnyframeError::ConfigError(format!("{}: {}", path.display(), e)))?;
+
+        contents.parse::<Self>().map_err(|e| match e {
+            error::AnyframeError::ConfigError(message) => {
+                error::AnyframeError::ConfigError(format!("{}: {}", path.display(), message))
+            }
+            e => e,
+        })
+    }
+
+    /// Create the selector registry: the built-in selectors, the custom selector
+    /// commands and the priority of the default selector
+    ///
+    /// A program and arguments given with a custom selector's name, as in
+    /// `--selector-command`, replace its program and are appended to its arguments.
+    pub fn selector_registry(&self) -> Result<SelectorRegistry> {
+        let mut registry = SelectorRegistry::default();
+        for (name, custom) in &self.selectors {
+            if registry.contains(name) {
+                return Err(error::AnyframeError::ConfigError(format!(
+                    "selectors.{}: a built-in selector has this name",
+                    name
+                )));
+            }
+            let (default_program, template) = custom.command_line().map_err(|e| match e {
+                error::AnyframeError::ConfigError(message) => error::AnyframeError::ConfigError(
+                    format!("selectors.{}.command: {}", name, message),
+                ),
+                e => e,
+            })?;
+
+            let (selector_name, custom) = (name.clone(), custom.clone());
+            registry.register(name, move |program, args| {
+                let program = program.unwrap_or_else(|| default_program.clone());
+                let args = template.iter().cloned().chain(args).collect();
+                Ok(Box::new(custom.selector(&selector_name, program, args)))
+            });
+        }
+
+        let Some(priority) = &self.selector.priority else {
+            return Ok(registry);
+        };
+        if let Some(unknown) = priority.iter().find(|name| !registry.contains(name)) {
+            return Err(error::AnyframeError::ConfigError(format!(
+                "selector.priority: unknown selector '{}' (available: {})",
+                unknown,
+                registry.names().join(", ")
+            )));
+        }
+        Ok(registry.priority(priority.clone()))
+    }
+
+    /// Get the options of a widget
+    #[must_use]
+    pub fn widget(&self, name: &str) -> WidgetConfig {
+        self.widgets.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Get the options of a custom widget
+    pub fn custom_widget(&self, name: &str) -> Result<&WidgetConfig> {
+        if BUILTIN_WIDGETS.contains(&name) {
+            return Err(error::AnyframeError::WidgetError(format!(
+                "'{}' is a built-in widget: run 'anyframe-rs {}' instead",
+                name, name
+            )));
+        }
+
+        self.widgets.get(name).ok_or_else(|| {
+            let custom: Vec<&str> = self.custom_widget_names().collect();
+            error::AnyframeError::WidgetError(format!(
+                "Unknown widget '{}' (custom widgets: {})",
+                name,
+                if custom.is_empty() {
+                    "none".to_string()
+                } else {
+                    custom.join(", ")
+                }
+            ))
+        })
+    }
+
+    /// Get the names of the custom widgets
+    pub fn custom_widget_names(&self) -> impl Iterator<Item = &str> {
+        self.widgets
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !BUILTIN_WIDGETS.contains(name))
+    }
+
+    /// Check the values that TOML deserialization cannot check
+    fn validate(&self) -> Result<()> {
+        validate_height("selector", self.selector.height.as_deref())?;
+
+        for (name, widget) in &self.widgets {
+            validate_height(&format!("widgets.{}", name), widget.height.as_deref())?;
+            if BUILTIN_WIDGETS.contains(&name.as_str()) {
+                Self::validate_builtin_widget(name, widget)?;
+            } else {
+                Self::validate_custom_widget(name, widget)?;
+            }
+
+            widget.key_actions().map_err(|e| match e {
+                error::AnyframeError::ConfigError(message) => {
+                    error::AnyframeError::ConfigError(format!("widgets.{}.{}", name, message))
+                }
+                e => e,
+            })?;
+        }
+
+        for (key, widget) in &self.bindings {
+            if !BUILTIN_WIDGETS.contains(&widget.as_str()) && !self.widgets.contains_key(widget) {
+                return Err(error::AnyframeError::ConfigError(format!(
+                    "bindings.\"{}\": unknown widget '{}' (available: {})",
+                    key,
+                    widget,
+                    BUILTIN_WIDGETS
+                        .iter()
+                        .copied()
+                        .chain(self.custom_widget_names())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+        }
+
+        self.selector_registry()?;
+
+        self.sources.history.backend()?;
+
+        Ok(())
+    }
+
+    /// Check the options of a built-in widget
+    fn validate_builtin_widget(name: &str, widget: &WidgetConfig) -> Result<()> {
+        for flag in widget.flags() {
+            if CUSTOM_WIDGET_OPTIONS.contains(&flag) {
+                return Err(error::AnyframeError::ConfigError(format!(
+                    "widgets.{}.{}: option only supported by custom widgets",
+                    name, flag
+                )));
+            }
+
+            let supported = WIDGET_FLAGS
+                .iter()
+                .find(|(f, _)| *f == flag)
+                .map_or(&[][..], |(_, widgets)| *widgets);
+            if !supported.contains(&name) {
+                return Err(error::AnyframeError::ConfigError(format!(
+                    "widgets.{}.{}: option not supported by this widget (supported by: {})",
+                    name,
+                    flag,
+                    supported.join(", ")
+                )));
+            }
+        }
+
+        if let Some(signal) = &widget.signal {
+            signal.parse::<Signal>().map_err(|e| match e {
+                error::AnyframeError::WidgetError(message) => error::AnyframeError::ConfigError(
+                    format!("widgets.{}.signal: {}", name, message),
+                ),
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check the options of a custom widget
+    fn validate_custom_widget(name: &str, widget: &WidgetConfig) -> Result<()> {
+        // The name becomes part of a zsh function name
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(error::AnyframeError::ConfigError(format!(
+                "widgets.{}: widget names may only contain letters, digits, '-' and '_'",
+                name
+            )));
+        }
+
+        for flag in widget.flags() {
+            if !CUSTOM_WIDGET_OPTIONS.contains(&flag) {
+                return Err(error::AnyframeError::ConfigError(format!(
+                    "widgets.{}.{}: option not supported by custom widgets",
+                    name, flag
+                )));
+            }
+        }
+
+        if widget.source.is_none() {
+            return Err(error::AnyframeError::ConfigError(format!(
+                "widgets.{}: custom widgets need a source (e.g. source = \"command:ls\"); \
+                 built-in widgets are: {}",
+                name,
+                BUILTIN_WIDGETS.join(", ")
+            )));
+        }
+        if widget.action.is_none() {
+            return Err(error::AnyframeError::ConfigError(format!(
+                "widgets.{}: custom widgets need an action ({})",
+                name,
+                actions::ACTION_NAMES.join(", ")
+            )));
+        }
+
+        let checked = widget
+            .source()
+            .map(drop)
+            .and_then(|()| widget.action().map(drop));
+        checked.map_err(|e| match e {
+            error::AnyframeError::SourceError(message)
+            | error::AnyframeError::ActionError(message)
+            | error::AnyframeError::ConfigError(message) => {
+                error::AnyframeError::ConfigError(format!("widgets.{}: {}", name, message))
+            }
+            e => e,
+        })
+    }
+
+    /// Render zsh code defining a ZLE widget for each custom widget
+    #[must_use]
+    pub fn widget_definitions(&self) -> String {
+        let mut commands = String::new();
+        for name in self.custom_widget_names() {
+            commands.push_str("anyframe-widget-");
+            commands.push_str(name);
+            commands.push_str("() {\n    anyframe-rs-widget run ");
+            commands.push_str(name);
+            commands.push_str("\n}\nzle -N anyframe-widget-");
+            commands.push_str(name);
+            commands.push('\n');
+        }
+        commands
+    }
+
+    /// Render the key bindings as `bindkey` commands for zsh
+    #[must_use]
+    pub fn bindkey_commands(&self) -> String {
+        let mut commands = String::new();
+        for (key, widget) in &self.bindings {
+            commands.push_str("bindkey ");
+            commands.push_str(&crate::actions::shell_quote(key));
+            commands.push_str(" anyframe-widget-");
+            commands.push_str(widget);
+            commands.push('\n');
+        }
+        commands
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = error::AnyframeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Self = toml::from_str(s)
+            .map_err(|e| error::AnyframeError::ConfigError(e.to_string().trim_end().to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Check that a height is a number of lines or a percentage, as fzf accepts it
+fn validate_height(table: &str, height: Option<&str>) -> Result<()> {
+    let Some(height) = height else {
+        return Ok(());
+    };
+    let number = height.strip_prefix('~').unwrap_or(height);
+    let number = number.strip_suffix('%').unwrap_or(number);
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error::AnyframeError::ConfigError(format!(
+            "{}.height: invalid height '{}' (expected lines such as 20 or a percentage such as 40%)",
+            table, height
+        )));
+    }
+    Ok(())
+}
+
+/// Expand a leading `~` to `$HOME`
+fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a configuration that must be rejected, and get its error message
+    fn error(toml: &str) -> String {
+        toml.parse::<Config>()
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert_eq!(
+            error("[selector]\ncolor = \"red\"\n"),
+            "Config error: TOML parse error at line 2, column 1\n  |\n2 | color = \"red\"\n  | ^^^^^\n\
+             unknown field `color`, expected one of `priority`, `name`, `command`, `prompt`, \
+             `height`, `layout`, `sort`, `popup`"
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_types() {
+        assert_eq!(
+            error("[selector]\nsort = \"yes\"\n"),
+            "Config error: TOML parse error at line 2, column 8\n  |\n2 | sort = \"yes\"\n  |        ^^^^^\n\
+             invalid type: string \"yes\", expected a boolean"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_heights() {
+        assert_eq!(
+            error("[selector]\nheight = \"tall\"\n"),
+            "Config error: selector.height: invalid height 'tall' \
+             (expected lines such as 20 or a percentage such as 40%)"
+        );
+        assert_eq!(
+            error("[widgets.cdr]\nheight = \"40%%\"\n"),
+            "Config error: widgets.cdr.height: invalid height '40%%' \
+             (expected lines such as 20 or a percentage such as 40%)"
+        );
+    }
+
+    #[test]
+    fn accepts_valid_heights() -> Result<()> {
+        for height in ["20", "40%", "~50%"] {
+            format!("[selector]\nheight = \"{}\"\n", height).parse::<Config>()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_layouts() {
+        assert_eq!(
+            error("[selector]\nlayout = \"sideways\"\n"),
+            "Config error: TOML parse error at line 2, column 10\n  |\n2 | layout = \"sideways\"\n  |          ^^^^^^^^^^\n\
+             unknown variant `sideways`, expected `default` or `reverse`"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert_eq!(
+            error("[widgets.cdr.keys]\nctrl-foo = { action = \"insert\" }\n"),
+            "Config error: widgets.cdr.keys.ctrl-foo: unknown key \
+             (expected ctrl-<letter>, alt-<character> or f1 to f12)"
+        );
+        assert_eq!(
+            error("[widgets.cdr.keys]\nctrl-o = { action = \"print\" }\n"),
+            "Config error: widgets.cdr.keys.ctrl-o: Unknown action 'print' \
+             (available: execute, insert, put, cd)"
+        );
+    }
+
+    #[test]
+    fn rejects_options_of_other_widgets() {
+        assert_eq!(
+            error("[widgets.cdr]\nremote = true\n"),
+            "Config error: widgets.cdr.remote: option not supported by this widget \
+             (supported by: checkout-git-branch, insert-git-branch)"
+        );
+        assert_eq!(
+            error("[widgets.cdr]\ntemplate = \"cd {}\"\n"),
+            "Config error: widgets.cdr.template: option only supported by custom widgets"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_signals() {
+        assert_eq!(
+            error("[widgets.kill]\nsignal = \"USR1\"\n"),
+            "Config error: widgets.kill.signal: Unknown signal 'USR1' \
+             (available: TERM, KILL, HUP, INT, STOP, CONT)"
+        );
+    }
+
+    #[test]
+    fn rejects_custom_widgets_without_a_source() {
+        assert_eq!(
+            error("[widgets.mine]\naction = \"insert\"\n"),
+            format!(
+                "Config error: widgets.mine: custom widgets need a source \
+                 (e.g. source = \"command:ls\"); built-in widgets are: {}",
+                BUILTIN_WIDGETS.join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_selectors_in_the_priority() {
+        assert_eq!(
+            error("[selector]\npriority = [\"fzf\", \"fzy\"]\n"),
+            format!(
+                "Config error: selector.priority: unknown selector 'fzy' (available: {})",
+                SelectorRegistry::default().names().join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn accepts_custom_selectors_in_the_priority() -> Result<()> {
+        "[selector]\npriority = [\"mypicker\", \"fzf\"]\n\n[selectors.mypicker]\ncommand = \"mypicker\"\n"
+            .parse::<Config>()?;
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_custom_selectors_shadowing_built_in_ones() {
+        assert_eq!(
+            error("[selectors.fzf]\ncommand = \"fzf --exact\"\n"),
+            "Config error: selectors.fzf: a built-in selector has this name"
+        );
+    }
+}
@@ -13,7 +13,7 @@
 //! anyframe-rs consists of four main components:
 //!
 //! - **Sources**: Provide data to be filtered (history, directories, processes, etc.)
-//! - **Selectors**: Interactive filtering tools (peco, percol, fzf, fzf-tmux)
+//! - **Selectors**: Interactive filtering tools (peco, percol, fzf, fzf-tmux, sk, custom commands)
 //! - **Actions**: Perform operations on selected items (execute, insert, put)
 //! - **Widgets**: Combine sources, selectors, and actions to create useful functionalities
 //!
@@ -21,32 +21,28 @@
 //!
 //! ```rust,no_run
 //! use anyframe_rs::{
-//!     actions::Execute,
 //!     selectors::{Peco, Percol, FzfTmux},
 //!     sources::History,
-//!     widgets::{ExecuteHistory, Widget},
+//!     widgets::{self, Picker, Widget},
 //! };
 //!
 //! fn main() -> anyframe_rs::Result<()> {
 //!     // Using Peco selector
-//!     let source = History;
+//!     let source = History::new(None);
 //!     let selector = Peco::new(None);
-//!     let action = Execute;
-//!     let widget = ExecuteHistory::new(source, selector, action);
+//!     let widget = widgets::execute_history(Picker::new(source, selector));
 //!     widget.run()?;
 //!
 //!     // Or using Percol selector
-//!     let source = History;
+//!     let source = History::new(None);
 //!     let selector = Percol::new(None);
-//!     let action = Execute;
-//!     let widget = ExecuteHistory::new(source, selector, action);
+//!     let widget = widgets::execute_history(Picker::new(source, selector));
 //!     widget.run()?;
 //!
 //!     // Or using FzfTmux selector
-//!     let source = History;
+//!     let source = History::new(None);
 //!     let selector = FzfTmux::new(None);
-//!     let action = Execute;
-//!     let widget = ExecuteHistory::new(source, selector, action);
+//!     let widget = widgets::execute_history(Picker::new(source, selector));
 //!     widget.run()?;
 //!     
 //!     Ok(())
@@ -73,12 +69,15 @@
 #![deny(clippy::must_use_candidate)]
 
 pub mod actions;
+pub mod config;
+pub mod matching;
 pub mod selectors;
 pub mod sources;
 pub mod widgets;
 
 /// Core error types for anyframe-rs
 pub mod error {
+    use std::process::{Command, ExitStatus};
     use thiserror::Error;
 
     /// Errors that can occur in anyframe-rs operations
@@ -100,10 +99,70 @@ pub mod error {
         #[error("Widget error: {0}")]
         WidgetError(String),
 
+        /// Error in the configuration file
+        #[error("Config error: {0}")]
+        ConfigError(String),
+
+        /// The user cancelled the selection
+        #[error("Selection cancelled")]
+        Cancelled,
+
+        /// No candidate matched the query or was selected
+        #[error("No item selected")]
+        NoMatch,
+
+        /// An external command exited unsuccessfully
+        #[error("{program} failed ({status}): {}", .stderr.trim_end())]
+        CommandFailed {
+            /// Program that was run
+            program: String,
+            /// Arguments of the program
+            args: Vec<String>,
+            /// Exit status of the program
+            status: ExitStatus,
+            /// Standard error output of the program
+            stderr: String,
+        },
+
         /// IO error
         #[error("IO error: {0}")]
         IoError(#[from] std::io::Error),
     }
+
+    impl AnyframeError {
+        /// Create a [`CommandFailed`](Self::CommandFailed) error for a command that exited
+        /// unsuccessfully
+        #[must_use]
+        pub fn command_failed(command: &Command, status: ExitStatus, stderr: &[u8]) -> Self {
+            Self::CommandFailed {
+                program: command.get_program().to_string_lossy().into_owned(),
+                args: command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect(),
+                status,
+                stderr: String::from_utf8_lossy(stderr).into_owned(),
+            }
+        }
+
+        /// Get the exit code of the anyframe-rs process failing with this error
+        ///
+        /// | Code | Meaning |
+        /// | --- | --- |
+        /// | 1 | [`NoMatch`](Self::NoMatch): nothing matched or was selected |
+        /// | 2 | Any other error, including an invalid command line |
+        /// | 3 | [`CommandFailed`](Self::CommandFailed): an external command failed |
+        /// | 130 | [`Cancelled`](Self::Cancelled): the user cancelled the selection |
+        #[must_use]
+        pub fn exit_code(&self) -> u8 {
+            match self {
+                Self::NoMatch => 1,
+                Self::CommandFailed { .. } => 3,
+                Self::Cancelled => 130,
+                _ => 2,
+            }
+        }
+    }
 }
 
 /// Result type for anyframe-rs operations
@@ -1,46 +1,245 @@
 //! anyframe-rs: A Rust implementation of anyframe, a peco/percol/fzf wrapper plugin for zsh
 
 use anyframe_rs::{
-    actions::{ChangeDirectory, Execute, Insert, Put},
-    selectors::Peco,
-    sources::{Cdr, GhqRepository, GitBranch, GitStatus, History, Process},
-    widgets::{
-        CdGhqRepository, Cdr as CdrWidget, CheckoutGitBranch, ExecuteHistory, GitAdd,
-        InsertGitBranch, Kill, PutHistory, Widget,
+    config::{Config, HistoryConfig, SelectorConfig, WidgetConfig},
+    error::AnyframeError,
+    selectors::{Filter, SelectOptions, Selector, SelectorRegistry},
+    sources::{
+        self, history::HistoryBackend, Cdr, Directory, Files, GhqRepository, GitBranch, GitStatus,
+        History, Process, Source,
     },
+    widgets::{self, Kill, Picker, Pipeline, Signal, Widget},
 };
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Selector used to filter the candidates (fzf, sk, peco, percol, fzf-tmux, native, or a
+    /// selector of the configuration file), overriding the configuration file
+    #[arg(long, global = true)]
+    selector: Option<String>,
+
+    /// Selector command line with arguments, e.g. 'fzf --exact', overriding the
+    /// configuration file
+    #[arg(long, global = true)]
+    selector_command: Option<String>,
+
+    /// Selector used when neither the command line nor the configuration file sets one,
+    /// as passed by the plugin from the :anyframe:selector: zstyle
+    #[arg(
+        long,
+        global = true,
+        env = "ANYFRAME_SELECTOR",
+        value_name = "SELECTOR"
+    )]
+    default_selector: Option<String>,
+
+    /// Selector command line used when neither the command line nor the configuration
+    /// file sets one
+    #[arg(
+        long,
+        global = true,
+        env = "ANYFRAME_SELECTOR_COMMAND",
+        value_name = "SELECTOR_COMMAND"
+    )]
+    default_selector_command: Option<String>,
+
+    /// Configuration file, ~/.config/anyframe-rs/config.toml by default
+    #[arg(long, global = true, env = "ANYFRAME_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Select the best match of the query without a terminal instead of running the selector
+    #[arg(long, global = true, value_name = "QUERY")]
+    select_first: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Args)]
+struct HistoryArgs {
+    /// Shell whose history is read (zsh, bash, fish), detected from $SHELL by default
+    #[arg(long)]
+    shell: Option<HistoryBackend>,
+    /// History file to read instead of the shell's default
+    #[arg(long)]
+    histfile: Option<PathBuf>,
+}
+
+impl HistoryArgs {
+    fn source(self, config: &HistoryConfig) -> anyframe_rs::Result<History> {
+        let source = History::new(self.histfile.or_else(|| config.histfile()));
+        Ok(match self.shell.or(config.backend()?) {
+            Some(backend) => source.with_backend(backend),
+            None => source,
+        })
+    }
+}
+
+/// Options of the git branch widgets
+///
+/// Each option overrides the widget's configuration, also when set to false, as in
+/// `--all=false`.
+#[derive(Args)]
+struct BranchArgs {
+    /// Include the current branch
+    #[arg(short, long, value_name = "BOOL")]
+    #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    include_current: Option<bool>,
+    /// List remote-tracking branches instead of local ones
+    #[arg(short, long, value_name = "BOOL")]
+    #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    remote: Option<bool>,
+    /// List both local and remote-tracking branches
+    #[arg(short, long, value_name = "BOOL")]
+    #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    all: Option<bool>,
+}
+
+impl BranchArgs {
+    fn source(self, defaults: &WidgetConfig) -> GitBranch {
+        GitBranch::new(
+            self.include_current
+                .or(defaults.include_current)
+                .unwrap_or(false),
+            self.remote.or(defaults.remote).unwrap_or(false),
+            self.all.or(defaults.all).unwrap_or(false),
+        )
+    }
+}
+
+/// Settings shared by all subcommands
+struct Context {
+    registry: SelectorRegistry,
+    config: Config,
+    selector: Option<String>,
+    selector_command: Option<String>,
+    default_selector: Option<String>,
+    default_selector_command: Option<String>,
+    select_first: Option<String>,
+}
+
+impl Context {
+    /// Build the selector of a widget
+    ///
+    /// The command line takes precedence over the widget's configuration, then over the
+    /// default selector of the configuration, then over the zstyle and the environment.
+    /// With `--select-first`, the headless filter selector is used instead.
+    fn selector(&self, widget: &str) -> anyframe_rs::Result<Box<dyn Selector>> {
+        if self.select_first.is_some() {
+            return Ok(Box::new(Filter::new().first(true)));
+        }
+
+        let selector = self.selector_config(widget);
+        self.registry
+            .resolve(selector.name.as_deref(), selector.command.as_deref())
+    }
+
+    /// Get the selector settings of a widget, with the selector of the command line
+    fn selector_config(&self, widget: &str) -> SelectorConfig {
+        let mut selector = self.config.widget(widget).selector(&self.config.selector);
+        if self.selector.is_some() || self.selector_command.is_some() {
+            selector.name.clone_from(&self.selector);
+            selector.command.clone_from(&self.selector_command);
+        } else if selector.name.is_none() && selector.command.is_none() {
+            selector.name.clone_from(&self.default_selector);
+            selector.command.clone_from(&self.default_selector_command);
+        }
+        selector
+    }
+
+    /// Run this invocation of anyframe-rs again in a tmux popup, if the widget asks for
+    /// one, and return its exit code
+    ///
+    /// Outside tmux, the selector runs inline. fzf-tmux opens the popup by itself.
+    #[cfg(unix)]
+    fn run_in_popup(&self, widget: &str) -> anyframe_rs::Result<Option<ExitCode>> {
+        use anyframe_rs::selectors::tmux;
+        use std::io::Write;
+        use std::path::Path;
+
+        let selector = self.selector_config(widget);
+        let Some(popup) = &selector.popup else {
+            return Ok(None);
+        };
+        if !tmux::in_tmux() || tmux::in_popup() || self.select_first.is_some() {
+            return Ok(None);
+        }
+
+        let name = match (&selector.name, &selector.command) {
+            (Some(name), _) => name.clone(),
+            (None, Some(command)) => shell_words::split(command)
+                .ok()
+                .and_then(|words| words.into_iter().next())
+                .and_then(|program| {
+                    Path::new(&program)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .unwrap_or_default(),
+            (None, None) => self.registry.discover()?,
+        };
+        if name == "fzf-tmux" {
+            return Ok(None);
+        }
+
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let output = tmux::run_in_popup(popup, &std::env::current_exe()?, &args)?;
+        std::io::stderr().write_all(&output.stderr)?;
+        std::io::stdout().write_all(&output.stdout)?;
+        Ok(Some(ExitCode::from(output.code)))
+    }
+
+    /// Run the selector inline, as tmux only runs on Unix
+    #[cfg(not(unix))]
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn run_in_popup(&self, _widget: &str) -> anyframe_rs::Result<Option<ExitCode>> {
+        Ok(None)
+    }
+
+    /// Get the selector options of a widget, such as its prompt and header
+    fn options(&self, widget: &str) -> SelectOptions {
+        let options = self.config.widget(widget).options(&self.config.selector);
+        match &self.select_first {
+            Some(query) => options.query(query.as_str()),
+            None => options,
+        }
+    }
+
+    /// Get the picker of a widget: its selector, selector options and alternate actions
+    fn picker<S: Source>(
+        &self,
+        widget: &str,
+        source: S,
+    ) -> anyframe_rs::Result<Picker<S, Box<dyn Selector>>> {
+        Ok(Picker::new(source, self.selector(widget)?)
+            .options(self.options(widget))
+            .keys(self.config.widget(widget).key_actions()?))
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Execute a command from history
-    ExecuteHistory,
+    ExecuteHistory {
+        #[command(flatten)]
+        history: HistoryArgs,
+    },
     /// Change directory to a ghq repository
     CdGhqRepository,
     /// Checkout a git branch
     CheckoutGitBranch {
-        #[arg(short, long)]
-        include_current: bool,
-        #[arg(short, long)]
-        remote: bool,
-        #[arg(short, long)]
-        all: bool,
+        #[command(flatten)]
+        branches: BranchArgs,
     },
     /// Insert a git branch
     InsertGitBranch {
-        #[arg(short, long)]
-        include_current: bool,
-        #[arg(short, long)]
-        remote: bool,
-        #[arg(short, long)]
-        all: bool,
+        #[command(flatten)]
+        branches: BranchArgs,
     },
     /// Add files to git
     GitAdd {
@@ -48,82 +247,311 @@ enum Commands {
         pattern: Option<String>,
     },
     /// Put a command from history
-    PutHistory,
+    PutHistory {
+        #[command(flatten)]
+        history: HistoryArgs,
+    },
     /// Change to a recent directory
     Cdr,
+    /// Change to a directory below the current one
+    CdDirectory {
+        /// Only descend this many levels below the current directory
+        #[arg(short = 'd', long, value_name = "DEPTH")]
+        max_depth: Option<usize>,
+        /// Include hidden directories
+        #[arg(short = 'H', long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        hidden: Option<bool>,
+        /// Follow symbolic links to directories
+        #[arg(short = 'L', long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        follow_symlinks: Option<bool>,
+        /// Do not respect .gitignore and .ignore files
+        #[arg(long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        no_ignore: Option<bool>,
+    },
+    /// Insert the paths of files below the current directory
+    InsertFilename {
+        /// List the files of the whole git repository
+        #[arg(short = 'g', long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        git_root: Option<bool>,
+        /// List the files with git ls-files inside git repositories
+        #[arg(long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        git_ls_files: Option<bool>,
+        /// Include hidden files
+        #[arg(short = 'H', long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        hidden: Option<bool>,
+        /// Do not respect .gitignore and .ignore files
+        #[arg(long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        no_ignore: Option<bool>,
+    },
     /// Kill a process
-    Kill,
+    Kill {
+        /// List the processes of all users
+        #[arg(short, long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        all_users: Option<bool>,
+        /// Show each process below its parent
+        #[arg(short, long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        tree: Option<bool>,
+        /// Only list the processes with this name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Only list the processes whose command line matches this regular expression
+        #[arg(short, long, value_name = "REGEX")]
+        pattern: Option<String>,
+        /// Send this signal (TERM, KILL, HUP, INT, STOP or CONT) instead of picking one
+        #[arg(short, long, value_name = "NAME")]
+        signal: Option<String>,
+        /// Signal the selected processes without confirmation
+        #[arg(long, value_name = "BOOL")]
+        #[arg(num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+        no_confirm: Option<bool>,
+        /// Send KILL to the processes still running this many seconds after TERM
+        #[arg(long, value_name = "SECONDS")]
+        escalate: Option<u64>,
+    },
+    /// Run a custom widget declared in the configuration file
+    Run {
+        /// Name of the widget, as in [widgets.<name>]
+        name: String,
+    },
+    /// Print the preview of a candidate, as run by the selectors' preview panes
+    Preview {
+        /// Name of the source, e.g. git-branch
+        source: String,
+        /// Value of the candidate
+        item: String,
+    },
+    /// Print the candidates of a source matching a query without a terminal, best first
+    Pick {
+        /// Source: a source name such as git-branch, or command:<command line>
+        #[arg(long)]
+        source: String,
+        /// Query matched against the candidates; without it, all candidates are printed
+        #[arg(long, value_name = "QUERY")]
+        filter: Option<String>,
+        /// Only print the best match
+        #[arg(long)]
+        first: bool,
+    },
+    /// Print the zsh code defining the custom widgets and key bindings of the configuration file
+    Bindings,
+}
+
+impl Commands {
+    /// Get the name of the widget run by this subcommand, as in the configuration file
+    fn widget(&self) -> Option<&str> {
+        match self {
+            Self::ExecuteHistory { .. } => Some("execute-history"),
+            Self::CdGhqRepository => Some("cd-ghq-repository"),
+            Self::CheckoutGitBranch { .. } => Some("checkout-git-branch"),
+            Self::InsertGitBranch { .. } => Some("insert-git-branch"),
+            Self::GitAdd { .. } => Some("git-add"),
+            Self::PutHistory { .. } => Some("put-history"),
+            Self::Cdr => Some("cdr"),
+            Self::CdDirectory { .. } => Some("cd-directory"),
+            Self::InsertFilename { .. } => Some("insert-filename"),
+            Self::Kill { .. } => Some("kill"),
+            Self::Run { name } => Some(name),
+            Self::Preview { .. } | Self::Pick { .. } | Self::Bindings => None,
+        }
+    }
 }
 
-fn main() -> anyframe_rs::Result<()> {
-    let cli = Cli::parse();
+/// Run anyframe-rs, exiting with the code of the error on failure
+///
+/// Cancelled selections and selections without a match exit quietly, so that the
+/// plugin only has to redraw the prompt.
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            if !matches!(e, AnyframeError::Cancelled | AnyframeError::NoMatch) {
+                eprintln!("anyframe-rs: {}", e);
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyframe_rs::Result<ExitCode> {
+    let config = Config::load(cli.config.as_deref())?;
+    let mut registry = config.selector_registry()?;
+    if let Some(cache) = SelectorRegistry::default_cache_path() {
+        registry = registry.cache(cache);
+    }
+    let context = Context {
+        registry,
+        config,
+        selector: cli.selector,
+        selector_command: cli.selector_command,
+        default_selector: cli.default_selector,
+        default_selector_command: cli.default_selector_command,
+        select_first: cli.select_first,
+    };
+    let config = &context.config;
 
-    match cli.command {
-        Commands::ExecuteHistory => {
-            let source = History;
-            let selector = Peco::new(None);
-            let action = Execute;
-            let widget = ExecuteHistory::new(source, selector, action);
-            widget.run()?;
+    if let Some(widget) = cli.command.widget() {
+        if let Some(code) = context.run_in_popup(widget)? {
+            return Ok(code);
+        }
+    }
+
+    let output = match cli.command {
+        Commands::ExecuteHistory { history } => {
+            let source = history.source(&config.sources.history)?;
+            let widget = widgets::execute_history(context.picker("execute-history", source)?);
+            widget.run()?
         }
         Commands::CdGhqRepository => {
             let source = GhqRepository;
-            let selector = Peco::new(None);
-            let action = ChangeDirectory;
-            let widget = CdGhqRepository::new(source, selector, action);
-            widget.run()?;
-        }
-        Commands::CheckoutGitBranch {
-            include_current,
-            remote,
-            all,
-        } => {
-            let source = GitBranch::new(!include_current, remote, all);
-            let selector = Peco::new(None);
-            let action = Execute;
-            let widget = CheckoutGitBranch::new(source, selector, action);
-            widget.run()?;
-        }
-        Commands::InsertGitBranch {
-            include_current,
-            remote,
-            all,
-        } => {
-            let source = GitBranch::new(!include_current, remote, all);
-            let selector = Peco::new(None);
-            let action = Insert;
-            let widget = InsertGitBranch::new(source, selector, action);
-            widget.run()?;
+            let widget = widgets::cd_ghq_repository(context.picker("cd-ghq-repository", source)?);
+            widget.run()?
+        }
+        Commands::CheckoutGitBranch { branches } => {
+            let source = branches.source(&config.widget("checkout-git-branch"));
+            let widget =
+                widgets::checkout_git_branch(context.picker("checkout-git-branch", source)?);
+            widget.run()?
+        }
+        Commands::InsertGitBranch { branches } => {
+            let source = branches.source(&config.widget("insert-git-branch"));
+            let widget = widgets::insert_git_branch(context.picker("insert-git-branch", source)?);
+            widget.run()?
         }
         Commands::GitAdd { pattern } => {
-            let source = GitStatus::new(pattern);
-            let selector = Peco::new(None);
-            let action = Execute;
-            let widget = GitAdd::new(source, selector, action);
-            widget.run()?;
-        }
-        Commands::PutHistory => {
-            let source = History;
-            let selector = Peco::new(None);
-            let action = Put::new(false);
-            let widget = PutHistory::new(source, selector, action);
-            widget.run()?;
+            let source = GitStatus::new(pattern.or(config.widget("git-add").pattern));
+            let widget = widgets::git_add(context.picker("git-add", source)?);
+            widget.run()?
+        }
+        Commands::PutHistory { history } => {
+            let source = history.source(&config.sources.history)?;
+            let widget = widgets::put_history(context.picker("put-history", source)?);
+            widget.run()?
         }
         Commands::Cdr => {
             let source = Cdr;
-            let selector = Peco::new(None);
-            let action = ChangeDirectory;
-            let widget = CdrWidget::new(source, selector, action);
-            widget.run()?;
-        }
-        Commands::Kill => {
-            let source = Process;
-            let selector = Peco::new(None);
-            let action = Execute;
-            let widget = Kill::new(source, selector, action);
-            widget.run()?;
+            let widget = widgets::cdr(context.picker("cdr", source)?);
+            widget.run()?
         }
-    }
+        Commands::CdDirectory {
+            max_depth,
+            hidden,
+            follow_symlinks,
+            no_ignore,
+        } => {
+            let defaults = config.widget("cd-directory");
+            let mut source = Directory::new(None)
+                .with_dirs_only(true)
+                .with_hidden(hidden.or(defaults.hidden).unwrap_or(false))
+                .with_follow_symlinks(
+                    follow_symlinks
+                        .or(defaults.follow_symlinks)
+                        .unwrap_or(false),
+                )
+                .with_ignore_files(!no_ignore.or(defaults.no_ignore).unwrap_or(false));
+            if let Some(max_depth) = max_depth.or(defaults.max_depth) {
+                source = source.with_max_depth(max_depth);
+            }
+            let widget = widgets::cd_directory(context.picker("cd-directory", source)?);
+            widget.run()?
+        }
+        Commands::InsertFilename {
+            git_root,
+            git_ls_files,
+            hidden,
+            no_ignore,
+        } => {
+            let defaults = config.widget("insert-filename");
+            let source = Files::new()
+                .with_git_root(git_root.or(defaults.git_root).unwrap_or(false))
+                .with_git_ls_files(git_ls_files.or(defaults.git_ls_files).unwrap_or(false))
+                .with_hidden(hidden.or(defaults.hidden).unwrap_or(false))
+                .with_ignore_files(!no_ignore.or(defaults.no_ignore).unwrap_or(false));
+            let widget = widgets::insert_filename(context.picker("insert-filename", source)?);
+            widget.run()?
+        }
+        Commands::Kill {
+            all_users,
+            tree,
+            name,
+            pattern,
+            signal,
+            no_confirm,
+            escalate,
+        } => {
+            let defaults = config.widget("kill");
+            let mut source = Process::new()
+                .with_all_users(all_users.or(defaults.all_users).unwrap_or(false))
+                .with_tree(tree.or(defaults.tree).unwrap_or(false));
+            if let Some(name) = name.or(defaults.name) {
+                source = source.with_name(name);
+            }
+            if let Some(pattern) = pattern.or(defaults.pattern) {
+                source = source.with_pattern(&pattern)?;
+            }
+            #[cfg(unix)]
+            if let Some(caller) = anyframe_rs::selectors::tmux::popup_caller() {
+                source = source.excluding(caller);
+            }
+            let mut widget = Kill::new(context.picker("kill", source)?)
+                .confirm(!no_confirm.or(defaults.no_confirm).unwrap_or(false));
+            if let Some(signal) = signal.or(defaults.signal) {
+                widget = widget.signal(signal.parse::<Signal>()?);
+            }
+            if let Some(seconds) = escalate.or(defaults.escalate) {
+                widget = widget.escalate(Duration::from_secs(seconds));
+            }
+            widget.run()?
+        }
+        Commands::Run { name } => {
+            let options = config.custom_widget(&name)?;
+            let source = options.source()?;
+            let action = options.action()?;
+            let mut widget = Pipeline::new(name.as_str(), context.picker(&name, source)?, action)
+                .multi(options.multi.unwrap_or(false));
+            if let Some(template) = &options.template {
+                widget = widget.template(template.as_str());
+            }
+            widget.run()?
+        }
+        Commands::Preview { source, item } => {
+            if let Some(preview) = sources::from_spec(&source)?.preview(&item)? {
+                print!("{}", preview);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Pick {
+            source,
+            filter,
+            first,
+        } => {
+            let source = sources::from_spec(&source)?;
+            let mut options = SelectOptions::new().multi(true);
+            if let Some(filter) = filter {
+                options = options.query(filter);
+            }
+            for candidate in Filter::new().first(first).select(&source, &options)?.items {
+                println!("{}", candidate.value);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Bindings => {
+            print!("{}", config.widget_definitions());
+            print!("{}", config.bindkey_commands());
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+
+    // The plugin evaluates this output in the calling shell's ZLE widget
+    print!("{}", output);
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
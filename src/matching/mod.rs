@@ -0,0 +1,503 @@
+//! Fuzzy matching and ranking
+//!
+//! Matches queries against candidates like fzf's extended search mode, so that candidates
+//! can be ranked without running a selector. The [`Native`](crate::selectors::Native)
+//! and [`Filter`](crate::selectors::Filter) selectors use this module, so scores are the
+//! same everywhere.
+//!
+//! A query is made of space-separated terms, all of which must match:
+//!
+//! | Term | Matches |
+//! | --- | --- |
+//! | `sbtrkt` | Texts containing `s`, `b`, `t`, `r`, `k` and `t` in this order |
+//! | `'wild` | Texts containing `wild` |
+//! | `^music` | Texts starting with `music` |
+//! | `.mp3$` | Texts ending with `.mp3` |
+//! | `^music$` | Texts that are exactly `music` |
+//! | `!fire` | Texts not containing `fire` |
+//! | `!^music`, `!.mp3$` | Texts not starting with `music`, not ending with `.mp3` |
+//!
+//! A term is case-sensitive only when it contains an uppercase letter (smart case).
+//!
+//! ```
+//! use anyframe_rs::matching::Pattern;
+//!
+//! let pattern = Pattern::parse("fb");
+//! let matched = pattern.matches("foo_bar").unwrap();
+//! assert_eq!(matched.positions, vec![0, 4]);
+//! assert!(pattern.matches("baz").is_none());
+//! ```
+
+use crate::sources::Candidate;
+
+/// Score of a matched character
+const SCORE_MATCH: i64 = 16;
+/// Penalty for the first character of a gap between matched characters
+const PENALTY_GAP_START: i64 = 3;
+/// Penalty for each further character of a gap
+const PENALTY_GAP_EXTENSION: i64 = 1;
+/// Bonus for a match at the start of a word
+const BONUS_BOUNDARY: i64 = 8;
+/// Bonus for a match at a camelCase or letter-to-digit transition
+const BONUS_CAMEL: i64 = 7;
+/// Bonus for a match right after the previous matched character
+const BONUS_CONSECUTIVE: i64 = 4;
+/// Multiplier of the bonus of the first matched character
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// Kind of a query term
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Characters in order, with anything between them
+    Fuzzy,
+    /// Substring
+    Exact,
+    /// Substring at the start of the text
+    Prefix,
+    /// Substring at the end of the text
+    Suffix,
+    /// Whole text
+    Equal,
+}
+
+/// Term of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Term {
+    kind: Kind,
+    inverse: bool,
+    chars: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Term {
+    /// Parse a term, returning `None` when nothing is left after its operators
+    fn parse(term: &str) -> Option<Self> {
+        let mut text = term;
+        let mut kind = Kind::Fuzzy;
+
+        let inverse = text.starts_with('!');
+        if inverse {
+            text = &text[1..];
+            kind = Kind::Exact;
+        }
+        if let Some(rest) = text.strip_prefix('\'') {
+            text = rest;
+            kind = Kind::Exact;
+        } else if let Some(rest) = text.strip_prefix('^') {
+            text = rest;
+            kind = Kind::Prefix;
+        }
+        if text.len() > 1 {
+            if let Some(rest) = text.strip_suffix('$') {
+                text = rest;
+                kind = if kind == Kind::Prefix {
+                    Kind::Equal
+                } else {
+                    Kind::Suffix
+                };
+            }
+        }
+        if text.is_empty() {
+            return None;
+        }
+
+        let case_sensitive = text.chars().any(char::is_uppercase);
+        Some(Self {
+            kind,
+            inverse,
+            chars: text.chars().map(|c| fold_case(c, case_sensitive)).collect(),
+            case_sensitive,
+        })
+    }
+
+    /// Match the term against a text, returning the score and the matched positions
+    fn matches(&self, text: &[char]) -> Option<(i64, Vec<usize>)> {
+        let folded: Vec<char> = text
+            .iter()
+            .map(|&c| fold_case(c, self.case_sensitive))
+            .collect();
+        let found = match self.kind {
+            Kind::Fuzzy => fuzzy_match(&self.chars, &folded, text),
+            Kind::Exact => exact_match(&self.chars, &folded, text),
+            Kind::Prefix => folded
+                .starts_with(&self.chars)
+                .then(|| substring_score(text, 0, self.chars.len())),
+            Kind::Suffix => folded
+                .ends_with(&self.chars)
+                .then(|| substring_score(text, folded.len() - self.chars.len(), self.chars.len())),
+            Kind::Equal => (folded == self.chars).then(|| substring_score(text, 0, text.len())),
+        };
+
+        match (found, self.inverse) {
+            (Some(found), false) => Some(found),
+            (None, true) => Some((0, Vec::new())),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed query
+///
+/// Terms are separated by whitespace; see the [module documentation](self) for the
+/// syntax. A pattern without terms matches every text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    terms: Vec<Term>,
+}
+
+impl Pattern {
+    /// Parse a query
+    #[must_use]
+    pub fn parse(query: &str) -> Self {
+        Self {
+            terms: query.split_whitespace().filter_map(Term::parse).collect(),
+        }
+    }
+
+    /// Check whether the pattern has no terms
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Match all terms of the pattern against a text
+    ///
+    /// The score is the sum of the scores of the terms, and the positions are the
+    /// indices of the matched characters (not bytes) of the text, in ascending order.
+    #[must_use]
+    pub fn matches(&self, text: &str) -> Option<Match> {
+        let text: Vec<char> = text.chars().collect();
+
+        let mut score = 0;
+        let mut positions = Vec::new();
+        for term in &self.terms {
+            let (term_score, term_positions) = term.matches(&text)?;
+            score += term_score;
+            positions.extend(term_positions);
+        }
+        positions.sort_unstable();
+        positions.dedup();
+
+        Some(Match { score, positions })
+    }
+}
+
+/// Result of matching a pattern against a text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Match {
+    /// Score of the match; higher is better
+    pub score: i64,
+    /// Indices of the matched characters, for highlighting
+    pub positions: Vec<usize>,
+}
+
+/// Candidate matching a pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranked {
+    /// Index of the candidate
+    pub index: usize,
+    /// Score of the match; higher is better
+    pub score: i64,
+    /// Indices of the matched characters of the candidate's display text
+    pub positions: Vec<usize>,
+}
+
+impl Ranked {
+    /// Create a ranked candidate from the match of its display text
+    #[must_use]
+    pub fn new(index: usize, matched: Match) -> Self {
+        Self {
+            index,
+            score: matched.score,
+            positions: matched.positions,
+        }
+    }
+}
+
+/// Match candidates' display texts against a query, returning the matches
+///
+/// With sorting, the best match comes first. An empty query matches every candidate
+/// except the header lines, which never match.
+#[must_use]
+pub fn rank(candidates: &[Candidate], query: &str, sort: bool) -> Vec<Ranked> {
+    let pattern = Pattern::parse(query);
+    let mut matches: Vec<Ranked> = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, candidate)| !candidate.header)
+        .filter_map(|(index, candidate)| {
+            pattern
+                .matches(&candidate.display)
+                .map(|matched| Ranked::new(index, matched))
+        })
+        .collect();
+    if sort {
+        sort_ranked(&mut matches);
+    }
+    matches
+}
+
+/// Sort matches by score, keeping the order of the candidates for equal scores
+pub fn sort_ranked(matches: &mut [Ranked]) {
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.index.cmp(&b.index)));
+}
+
+/// Find the best-scoring fuzzy match of a pattern
+///
+/// Like fzf's v2 algorithm, every alignment of the pattern is considered, rather than
+/// only the first occurrence: `score[i][j]` is the best score of the first `i + 1`
+/// characters of the pattern with the last one matched at `j`, either right after the
+/// previous one or after a gap.
+fn fuzzy_match(pattern: &[char], folded: &[char], text: &[char]) -> Option<(i64, Vec<usize>)> {
+    // Trim the text to where a match can be, which also rejects most texts early
+    let first = folded.iter().position(|&c| c == pattern[0])?;
+    let last = folded
+        .iter()
+        .rposition(|&c| c == pattern[pattern.len() - 1])?;
+    if last < first {
+        return None;
+    }
+    let window = &folded[first..=last];
+    let bonuses: Vec<i64> = (first..=last).map(|i| boundary_bonus(text, i)).collect();
+
+    let width = window.len();
+    let mut scores = vec![None; pattern.len() * width];
+    let mut from = vec![0; pattern.len() * width];
+    for (j, &c) in window.iter().enumerate() {
+        if c == pattern[0] {
+            scores[j] = Some(SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER);
+        }
+    }
+
+    for i in 1..pattern.len() {
+        let (previous, row) = scores.split_at_mut(i * width);
+        let previous = &previous[(i - 1) * width..];
+        let row = &mut row[..width];
+
+        // Best score of a previous match followed by a gap ending before `j`
+        let mut gap: Option<(i64, usize)> = None;
+        for j in 1..width {
+            if j >= 2 {
+                let extended = gap.map(|(score, k)| (score - PENALTY_GAP_EXTENSION, k));
+                let started = previous[j - 2].map(|score| (score - PENALTY_GAP_START, j - 2));
+                gap = match (extended, started) {
+                    (Some(a), Some(b)) => Some(if b.0 >= a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                };
+            }
+            if window[j] != pattern[i] {
+                continue;
+            }
+
+            let consecutive = previous[j - 1].map(|score| (score + BONUS_CONSECUTIVE, j - 1));
+            let best = match (consecutive, gap) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+            if let Some((score, k)) = best {
+                row[j] = Some(score + SCORE_MATCH + bonuses[j]);
+                from[i * width + j] = k;
+            }
+        }
+    }
+
+    // Take the best end, the first one for equal scores, and trace the match back
+    let last_row = &scores[(pattern.len() - 1) * width..];
+    let (mut j, score) = last_row
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .fold(None, |best: Option<(usize, i64)>, (j, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((j, score)),
+        })?;
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = first + j;
+        j = from[i * width + j];
+    }
+
+    Some((score, positions))
+}
+
+/// Find the best-scoring occurrence of a substring
+fn exact_match(pattern: &[char], folded: &[char], text: &[char]) -> Option<(i64, Vec<usize>)> {
+    if pattern.len() > folded.len() {
+        return None;
+    }
+    (0..=folded.len() - pattern.len())
+        .filter(|&start| folded[start..].starts_with(pattern))
+        .map(|start| substring_score(text, start, pattern.len()))
+        .fold(None, |best, found| match best {
+            Some((best_score, _)) if best_score >= found.0 => best,
+            _ => Some(found),
+        })
+}
+
+/// Score a match of consecutive characters
+fn substring_score(text: &[char], start: usize, len: usize) -> (i64, Vec<usize>) {
+    let positions: Vec<usize> = (start..start + len).collect();
+    let score = positions
+        .iter()
+        .map(|&i| {
+            if i == start {
+                SCORE_MATCH + boundary_bonus(text, i) * BONUS_FIRST_CHAR_MULTIPLIER
+            } else {
+                SCORE_MATCH + boundary_bonus(text, i) + BONUS_CONSECUTIVE
+            }
+        })
+        .sum();
+    (score, positions)
+}
+
+/// Lowercase a character unless the match is case-sensitive
+fn fold_case(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// Bonus for matching the character at `i`, depending on the previous character
+fn boundary_bonus(text: &[char], i: usize) -> i64 {
+    let c = text[i];
+    let Some(&previous) = i.checked_sub(1).and_then(|p| text.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+
+    let camel_case = previous.is_lowercase() && c.is_uppercase();
+    let starts_number = !previous.is_numeric() && c.is_numeric();
+    if !previous.is_alphanumeric() && c.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if camel_case || starts_number {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        Pattern::parse(query)
+            .matches(text)
+            .map(|matched| matched.positions)
+    }
+
+    fn ranked<'a>(candidates: &'a [Candidate], query: &str, sort: bool) -> Vec<&'a str> {
+        rank(candidates, query, sort)
+            .into_iter()
+            .map(|ranked| candidates[ranked.index].value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(positions("foo", "FooBar").is_some());
+        assert!(positions("Foo", "FooBar").is_some());
+        assert!(positions("Foo", "foobar").is_none());
+        assert!(positions("!Foo", "foobar").is_some());
+    }
+
+    #[test]
+    fn fuzzy_terms_match_characters_in_order() {
+        assert_eq!(positions("fb", "foo_bar"), Some(vec![0, 4]));
+        assert_eq!(positions("bf", "foo_bar"), None);
+        // The alignment on word boundaries wins over the first occurrence
+        assert_eq!(positions("fb", "fxb foo_bar"), Some(vec![4, 8]));
+    }
+
+    #[test]
+    fn exact_terms_match_substrings() {
+        assert_eq!(positions("'oba", "foobar"), Some(vec![2, 3, 4]));
+        assert_eq!(positions("'fb", "foo_bar"), None);
+        // The occurrence at a word boundary wins
+        assert_eq!(positions("'bar", "xbar bar"), Some(vec![5, 6, 7]));
+    }
+
+    #[test]
+    fn prefix_and_suffix_terms_anchor_the_match() {
+        assert_eq!(positions("^foo", "foobar"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("^foo", "barfoo"), None);
+        assert_eq!(positions("bar$", "foobar"), Some(vec![3, 4, 5]));
+        assert_eq!(positions("bar$", "barfoo"), None);
+    }
+
+    #[test]
+    fn prefix_and_suffix_together_match_the_whole_text() {
+        assert_eq!(positions("^foo$", "foo"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("^foo$", "FOO"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("^foo$", "foobar"), None);
+        assert_eq!(positions("^foo$", "xfoo"), None);
+        assert_eq!(positions("!^foo$", "foobar"), Some(vec![]));
+        assert_eq!(positions("!^foo$", "foo"), None);
+    }
+
+    #[test]
+    fn negated_terms_exclude_matches() {
+        assert_eq!(positions("!fire", "fireworks"), None);
+        assert_eq!(positions("!fire", "water"), Some(vec![]));
+        // Negated terms are exact, so scattered characters do not exclude
+        assert_eq!(positions("!fb", "foo_bar"), Some(vec![]));
+        assert_eq!(positions("!^music", "music.mp3"), None);
+        assert_eq!(positions("!^music", "my music.mp3"), Some(vec![]));
+        assert_eq!(positions("!.mp3$", "music.mp3"), None);
+        assert_eq!(positions("!.mp3$", "music.ogg"), Some(vec![]));
+    }
+
+    #[test]
+    fn all_terms_must_match() {
+        assert_eq!(
+            positions("^mu mp3$", "music.mp3"),
+            Some(vec![0, 1, 6, 7, 8])
+        );
+        assert_eq!(positions("mu !mp3", "music.mp3"), None);
+        assert_eq!(positions("mu ogg", "music.mp3"), None);
+    }
+
+    #[test]
+    fn empty_patterns_match_everything() {
+        let pattern = Pattern::parse("  ' ^ ! ");
+        assert!(pattern.is_empty());
+        assert_eq!(pattern.matches("anything"), Some(Match::default()));
+    }
+
+    #[test]
+    fn boundary_and_consecutive_matches_rank_first() {
+        let candidates = [
+            Candidate::new("xaxxbxxc"),
+            Candidate::new("xxabcxx"),
+            Candidate::new("xx abc"),
+            Candidate::new("xyz"),
+        ];
+        assert_eq!(
+            ranked(&candidates, "abc", true),
+            ["xx abc", "xxabcxx", "xaxxbxxc"]
+        );
+        assert_eq!(
+            ranked(&candidates, "abc", false),
+            ["xaxxbxxc", "xxabcxx", "xx abc"]
+        );
+    }
+
+    #[test]
+    fn equal_scores_keep_the_order_of_the_candidates() {
+        let candidates = [Candidate::new("feature"), Candidate::new("feat")];
+        assert_eq!(ranked(&candidates, "feat", true), ["feature", "feat"]);
+    }
+
+    #[test]
+    fn headers_are_never_ranked() {
+        let candidates = [
+            Candidate::header_line("NAME"),
+            Candidate::new("name"),
+            Candidate::new("other"),
+        ];
+        assert_eq!(ranked(&candidates, "", true), ["name", "other"]);
+        assert_eq!(ranked(&candidates, "name", true), ["name"]);
+        assert_eq!(ranked(&candidates, "!other", true), ["name"]);
+    }
+}
@@ -0,0 +1,236 @@
+//! Custom command selector
+//!
+//! Runs any picker that reads candidates from stdin and prints the selected lines, so
+//! that in-house tools can be used without writing Rust code.
+
+use super::{run_command, ExitCodes, LineFormat, SelectOptions, Selection, Selector};
+use crate::{sources::Source, Result};
+use std::process::Command;
+
+/// Custom command selector
+///
+/// The arguments are templates in which `{query}`, `{prompt}` and `{multi}` stand for
+/// the initial query, the prompt and, in multi-select mode, the
+/// [multi-select flag](Self::multi_flag). An argument is left out when one of its
+/// placeholders has no value, so that `--query={query}` is only passed with a query.
+///
+/// Candidates are written one display text per line, and the lines printed by the
+/// command are mapped back to them. Exit codes other than 0 fail the selection, unless
+/// they are configured to mean a cancelled selection or no match.
+#[derive(Debug, Clone)]
+pub struct CustomCommand {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    multi_flag: Option<String>,
+    cancel_exit_code: Option<i32>,
+    no_match_exit_code: Option<i32>,
+}
+
+impl CustomCommand {
+    /// Create a selector running a program, registered under the given name
+    #[must_use]
+    pub fn new(name: impl Into<String>, program: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            program: program.into(),
+            args: Vec::new(),
+            multi_flag: None,
+            cancel_exit_code: None,
+            no_match_exit_code: None,
+        }
+    }
+
+    /// Set the argument templates passed to the program
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Set the argument replacing `{multi}` in multi-select mode, such as `--multi`
+    #[must_use]
+    pub fn multi_flag(mut self, flag: impl Into<String>) -> Self {
+        self.multi_flag = Some(flag.into());
+        self
+    }
+
+    /// Set the exit code of the program meaning that the user cancelled the selection
+    #[must_use]
+    pub fn cancel_exit_code(mut self, code: i32) -> Self {
+        self.cancel_exit_code = Some(code);
+        self
+    }
+
+    /// Set the exit code of the program meaning that nothing matched the query
+    #[must_use]
+    pub fn no_match_exit_code(mut self, code: i32) -> Self {
+        self.no_match_exit_code = Some(code);
+        self
+    }
+
+    /// Expand the placeholders of an argument, or return `None` to leave it out
+    fn expand(&self, template: &str, options: &SelectOptions) -> Option<String> {
+        let multi = if options.multi {
+            self.multi_flag.as_deref()
+        } else {
+            None
+        };
+        let placeholders = [
+            ("query", options.query.as_deref()),
+            ("prompt", options.prompt.as_deref()),
+            ("multi", multi),
+        ];
+
+        // Expand in a single pass, so that values are never expanded again
+        let mut arg = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            arg.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+            let expanded = placeholders.iter().find_map(|(name, value)| {
+                rest.strip_prefix(name)
+                    .and_then(|after| after.strip_prefix('}'))
+                    .map(|after| (after, *value))
+            });
+            if let Some((after, value)) = expanded {
+                arg.push_str(value?);
+                rest = after;
+            } else {
+                arg.push('{');
+            }
+        }
+        arg.push_str(rest);
+        Some(arg)
+    }
+}
+
+impl Selector for CustomCommand {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(
+            self.args
+                .iter()
+                .filter_map(|template| self.expand(template, options)),
+        );
+
+        let exit_codes = ExitCodes {
+            cancelled: self.cancel_exit_code,
+            no_match: self.no_match_exit_code,
+        };
+        run_command(
+            cmd,
+            &self.name,
+            source,
+            LineFormat::Display,
+            &exit_codes,
+            false,
+        )
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn program(&self) -> Option<&str> {
+        Some(&self.program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(query: Option<&str>, prompt: Option<&str>, multi: bool) -> SelectOptions {
+        SelectOptions {
+            query: query.map(str::to_string),
+            prompt: prompt.map(str::to_string),
+            multi,
+            ..SelectOptions::default()
+        }
+    }
+
+    #[test]
+    fn substitutes_the_placeholders() {
+        let selector = CustomCommand::new("pick", "pick").multi_flag("--multi");
+        let options = options(Some("main"), Some("branch> "), true);
+        assert_eq!(
+            selector.expand("--query={query}", &options).as_deref(),
+            Some("--query=main")
+        );
+        assert_eq!(
+            selector.expand("{prompt}", &options).as_deref(),
+            Some("branch> ")
+        );
+        assert_eq!(
+            selector.expand("{multi}", &options).as_deref(),
+            Some("--multi")
+        );
+        assert_eq!(
+            selector.expand("{query}:{prompt}", &options).as_deref(),
+            Some("main:branch> ")
+        );
+    }
+
+    #[test]
+    fn leaves_other_braces_unchanged() {
+        let selector = CustomCommand::new("pick", "pick");
+        let options = options(Some("q"), None, false);
+        assert_eq!(
+            selector.expand("{other} {query", &options).as_deref(),
+            Some("{other} {query")
+        );
+        assert_eq!(
+            selector.expand("{{query}}", &options).as_deref(),
+            Some("{q}")
+        );
+    }
+
+    #[test]
+    fn drops_arguments_without_a_value() {
+        let selector = CustomCommand::new("pick", "pick");
+        let options = options(None, Some("> "), false);
+        assert_eq!(selector.expand("--query={query}", &options), None);
+        assert_eq!(selector.expand("--prompt={prompt}{query}", &options), None);
+        assert_eq!(
+            selector.expand("--prompt={prompt}", &options).as_deref(),
+            Some("--prompt=> ")
+        );
+    }
+
+    #[test]
+    fn never_expands_values_again() {
+        let selector = CustomCommand::new("pick", "pick").multi_flag("{query}");
+        let options = options(Some("{prompt}"), Some("{query}"), true);
+        assert_eq!(
+            selector.expand("{query}", &options).as_deref(),
+            Some("{prompt}")
+        );
+        assert_eq!(
+            selector.expand("{prompt}", &options).as_deref(),
+            Some("{query}")
+        );
+        assert_eq!(
+            selector.expand("{multi}", &options).as_deref(),
+            Some("{query}")
+        );
+    }
+
+    #[test]
+    fn passes_the_multi_flag_only_in_multi_select_mode() {
+        let with_flag = CustomCommand::new("pick", "pick").multi_flag("-m");
+        let without_flag = CustomCommand::new("pick", "pick");
+        let single_select = options(None, None, false);
+        let multi_select = options(None, None, true);
+        assert_eq!(
+            with_flag.expand("{multi}", &multi_select).as_deref(),
+            Some("-m")
+        );
+        assert_eq!(with_flag.expand("{multi}", &single_select), None);
+        assert_eq!(without_flag.expand("{multi}", &multi_select), None);
+        assert_eq!(
+            with_flag.expand("--no-multi", &multi_select).as_deref(),
+            Some("--no-multi")
+        );
+    }
+}
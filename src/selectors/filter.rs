@@ -0,0 +1,144 @@
+//! Headless selector
+//!
+//! Ranks the candidates against the query without a terminal, like `fzf --filter`, so
+//! that sources and widgets can be used from scripts and tests.
+
+use super::{SelectOptions, Selection, Selector};
+use crate::{error, matching, sources::Source, Result};
+
+/// Filter selector
+///
+/// Matches the candidates against [`SelectOptions::query`] with the [`matching`] module,
+/// like the [`Native`](super::Native) selector. Returns the best match, or every match in
+/// multi-select mode, best first unless sorting is disabled. Without a query, all
+/// candidates match in the order of the source.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    first: bool,
+}
+
+impl Filter {
+    /// Create a new Filter selector
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only return the best match, even in multi-select mode
+    #[must_use]
+    pub fn first(mut self, first: bool) -> Self {
+        self.first = first;
+        self
+    }
+}
+
+impl Selector for Filter {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        let candidates = source.candidates()?;
+        let query = options.query.as_deref().unwrap_or("");
+        let mut matches = matching::rank(&candidates, query, options.sort.unwrap_or(true));
+
+        if self.first || !options.multi {
+            matches.truncate(1);
+        }
+        if matches.is_empty() {
+            return Err(error::AnyframeError::NoMatch);
+        }
+
+        Ok(Selection::new(
+            matches
+                .into_iter()
+                .map(|matched| candidates[matched.index].clone())
+                .collect(),
+        ))
+    }
+
+    fn name(&self) -> &'static str {
+        "filter"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::Candidate;
+
+    fn branches() -> Vec<Candidate> {
+        vec![
+            Candidate::header_line("BRANCH"),
+            Candidate::new("master"),
+            Candidate::new("blog-post"),
+            Candidate::new("flatbox-grip"),
+            Candidate::new("feature/login"),
+        ]
+    }
+
+    fn values(selection: &Selection) -> Vec<&str> {
+        selection
+            .items
+            .iter()
+            .map(|candidate| candidate.value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn returns_the_best_match() -> Result<()> {
+        let selection = Filter::new().select(&branches(), &SelectOptions::new().query("log"))?;
+        assert_eq!(values(&selection), ["feature/login"]);
+        assert_eq!(selection.key, None);
+        Ok(())
+    }
+
+    #[test]
+    fn returns_every_match_best_first_in_multi_select_mode() -> Result<()> {
+        let options = SelectOptions::new().query("log").multi(true);
+        let selection = Filter::new().select(&branches(), &options)?;
+        assert_eq!(
+            values(&selection),
+            ["feature/login", "blog-post", "flatbox-grip"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_source_order_without_sorting() -> Result<()> {
+        let options = SelectOptions::new().query("log").multi(true).sort(false);
+        let selection = Filter::new().select(&branches(), &options)?;
+        assert_eq!(
+            values(&selection),
+            ["blog-post", "flatbox-grip", "feature/login"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn first_limits_multi_select_to_the_best_match() -> Result<()> {
+        let options = SelectOptions::new().query("log").multi(true);
+        let selection = Filter::new().first(true).select(&branches(), &options)?;
+        assert_eq!(values(&selection), ["feature/login"]);
+        Ok(())
+    }
+
+    #[test]
+    fn matches_every_candidate_but_headers_without_a_query() -> Result<()> {
+        let options = SelectOptions::new().multi(true);
+        let selection = Filter::new().select(&branches(), &options)?;
+        assert_eq!(
+            values(&selection),
+            ["master", "blog-post", "flatbox-grip", "feature/login"]
+        );
+
+        let selection = Filter::new().select(&branches(), &SelectOptions::new())?;
+        assert_eq!(values(&selection), ["master"]);
+        Ok(())
+    }
+
+    #[test]
+    fn fails_without_a_match() {
+        let result = Filter::new().select(&branches(), &SelectOptions::new().query("xyz"));
+        assert!(matches!(result, Err(error::AnyframeError::NoMatch)));
+
+        let result = Filter::new().select(&branches(), &SelectOptions::new().query("BRANCH"));
+        assert!(matches!(result, Err(error::AnyframeError::NoMatch)));
+    }
+}
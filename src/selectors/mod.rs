@@ -1,23 +1,477 @@
 //! Selectors for anyframe-rs
 //!
 //! Selectors are interactive filtering tools like peco, percol, fzf, and fzf-tmux.
+//! The [`Native`] selector needs no external tool, and the [`Filter`] selector needs no
+//! terminal.
+
+use crate::{
+    error,
+    sources::{Candidate, CandidateSink, Source},
+    Result,
+};
+use std::io::{Read, Write};
+use std::process::{ChildStdin, Command};
+
+mod custom;
+mod filter;
+mod native;
+mod registry;
+#[cfg(unix)]
+pub mod tmux;
+
+pub use custom::CustomCommand;
+pub use filter::Filter;
+pub use native::Native;
+pub use registry::{SelectorRegistry, DEFAULT_PRIORITY};
+
+/// Arrangement of the prompt and the candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Prompt at the bottom, candidates listed upwards (fzf's default)
+    Default,
+    /// Prompt at the top, candidates listed downwards
+    Reverse,
+}
+
+/// Size and position of a tmux popup, in tmux's formats
+///
+/// Sizes are lines or columns, or percentages such as `80%`. Positions are columns or
+/// lines, or tmux's position letters such as `C` for the center. Unset values keep
+/// tmux's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Popup {
+    /// Width of the popup
+    pub width: Option<String>,
+    /// Height of the popup
+    pub height: Option<String>,
+    /// Column of the popup
+    pub x: Option<String>,
+    /// Line of the popup
+    pub y: Option<String>,
+}
 
-use crate::{error, Result};
-use std::io::Write;
-use std::process::Command;
+/// Options of a selection
+///
+/// Each selector translates the options into its own command line flags and silently
+/// ignores the ones the tool does not support. Unset options keep the tool's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectOptions {
+    /// Initial query
+    pub query: Option<String>,
+    /// Allow selecting more than one candidate
+    pub multi: bool,
+    /// Prompt, such as `branch> `
+    pub prompt: Option<String>,
+    /// Header shown above the candidates
+    pub header: Option<String>,
+    /// Height of the selector, such as `40%` or `20`, instead of the full screen
+    pub height: Option<String>,
+    /// Arrangement of the prompt and the candidates
+    pub layout: Option<Layout>,
+    /// Whether to sort the candidates by score, `false` keeping the order of the source
+    pub sort: Option<bool>,
+    /// Keys accepting the selection besides Enter, such as `ctrl-o`
+    ///
+    /// See [`is_key_name`] for the supported names.
+    pub expect: Vec<String>,
+    /// Show the selector in a tmux popup (inside tmux only)
+    ///
+    /// fzf-tmux opens the popup itself. Other selectors ignore this option, as the
+    /// whole widget is run in the popup with [`tmux::run_in_popup`].
+    pub popup: Option<Popup>,
+}
+
+impl SelectOptions {
+    /// Create options with every setting left to the selector's default
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the initial query
+    #[must_use]
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Allow selecting more than one candidate
+    #[must_use]
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
+
+    /// Set the prompt
+    #[must_use]
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Set the header
+    #[must_use]
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Set the height
+    #[must_use]
+    pub fn height(mut self, height: impl Into<String>) -> Self {
+        self.height = Some(height.into());
+        self
+    }
+
+    /// Set the layout
+    #[must_use]
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Set whether to sort the candidates by score
+    #[must_use]
+    pub fn sort(mut self, sort: bool) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Set the keys accepting the selection besides Enter
+    #[must_use]
+    pub fn expect(mut self, keys: Vec<String>) -> Self {
+        self.expect = keys;
+        self
+    }
+
+    /// Show the selector in a tmux popup
+    #[must_use]
+    pub fn popup(mut self, popup: Popup) -> Self {
+        self.popup = Some(popup);
+        self
+    }
+}
+
+/// Check whether a key name can be expected by the selectors
+///
+/// The names follow fzf: `ctrl-a` to `ctrl-z`, `alt-` followed by a character, and
+/// `f1` to `f12`.
+#[must_use]
+pub fn is_key_name(name: &str) -> bool {
+    if let Some(letter) = name.strip_prefix("ctrl-") {
+        return letter.len() == 1 && letter.chars().all(|c| c.is_ascii_lowercase());
+    }
+    if let Some(character) = name.strip_prefix("alt-") {
+        return character.chars().count() == 1;
+    }
+    name.strip_prefix('f')
+        .and_then(|number| number.parse::<u8>().ok())
+        .is_some_and(|number| (1..=12).contains(&number) && !name.starts_with("f0"))
+}
+
+/// Result of a selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    /// Expected key that accepted the selection, or `None` for Enter
+    pub key: Option<String>,
+    /// Selected candidates
+    pub items: Vec<Candidate>,
+}
+
+impl Selection {
+    /// Create a selection accepted with Enter
+    #[must_use]
+    pub fn new(items: Vec<Candidate>) -> Self {
+        Self { key: None, items }
+    }
+
+    /// Set the expected key that accepted the selection
+    #[must_use]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+}
 
 /// Trait for selectors
 pub trait Selector {
-    /// Run the selector with the given input
-    fn select(&self, input: &str, query: Option<&str>) -> Result<String>;
+    /// Run the selector on the candidates of the source and return the selected ones
+    ///
+    /// The source is streamed into the selector while it is already interactive.
+    /// The selector shows the `display` text of each candidate. When `options.multi`
+    /// is set, the user may select more than one candidate. The selection also tells
+    /// which of the `options.expect` keys accepted it, if any; selectors that cannot
+    /// expect keys only accept with Enter.
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection>;
 
     /// Get the name of the selector
     fn name(&self) -> &str;
+
+    /// Get the external program run by the selector, if any
+    ///
+    /// The registry checks that the program exists before using the selector.
+    fn program(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<T: Selector + ?Sized> Selector for Box<T> {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        (**self).select(source, options)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn program(&self) -> Option<&str> {
+        (**self).program()
+    }
+}
+
+/// Exit codes with a special meaning for a selector command
+struct ExitCodes {
+    /// Exit code returned when the user cancels the selection
+    cancelled: Option<i32>,
+    /// Exit code returned when nothing matched the query
+    no_match: Option<i32>,
+}
+
+/// How candidates are written to a selector command and mapped back from its output
+#[derive(Clone, Copy)]
+enum LineFormat {
+    /// Write the display text only and map selected lines back by their text
+    Display,
+    /// Prefix the display text with the candidate index and value, separated by tabs
+    ///
+    /// The selector is expected to hide the prefix (e.g. with fzf's `--with-nth 3..`)
+    /// and to print the whole line back. The value is only there for previews.
+    Indexed,
+}
+
+impl LineFormat {
+    /// Format a candidate as a single input line for the selector
+    fn format(self, index: usize, candidate: &Candidate) -> String {
+        // A candidate is always shown on a single line
+        let display = candidate.display.replace(['\n', '\r'], " ");
+        match self {
+            Self::Display => format!("{}\n", display),
+            Self::Indexed => format!(
+                "{}\t{}\t{}\n",
+                index,
+                candidate.value.replace(['\n', '\r', '\t'], " "),
+                display
+            ),
+        }
+    }
+
+    /// Find the index of the candidate a selected line refers to
+    ///
+    /// Header lines are never found, so selecting one selects nothing.
+    fn parse(self, line: &str, candidates: &[Candidate]) -> Option<usize> {
+        match self {
+            Self::Display => candidates
+                .iter()
+                .position(|c| !c.header && c.display.replace(['\n', '\r'], " ") == line),
+            Self::Indexed => line
+                .split_once('\t')
+                .and_then(|(index, _)| index.parse::<usize>().ok())
+                .filter(|&index| candidates.get(index).is_some_and(|c| !c.header)),
+        }
+    }
+}
+
+/// Sink writing streamed candidates to a selector's stdin
+struct StdinSink {
+    stdin: ChildStdin,
+    format: LineFormat,
+    candidates: Vec<Candidate>,
+}
+
+impl CandidateSink for StdinSink {
+    fn push(&mut self, candidate: Candidate) -> Result<()> {
+        let line = self.format.format(self.candidates.len(), &candidate);
+        self.stdin
+            .write_all(line.as_bytes())
+            .map_err(error::AnyframeError::IoError)?;
+        self.candidates.push(candidate);
+        Ok(())
+    }
+}
+
+/// Add fzf's options hiding the index and value of [`LineFormat::Indexed`] lines,
+/// fixing the header lines and previewing the candidates of the source
+///
+/// The height is left to the caller, as fzf-tmux takes it with its own flag.
+fn add_fzf_args(cmd: &mut Command, source: &dyn Source, options: &SelectOptions) {
+    cmd.args(["--delimiter", "\t", "--with-nth", "3.."]);
+    let header_lines = source.header_lines();
+    if header_lines > 0 {
+        cmd.arg(format!("--header-lines={}", header_lines));
+    }
+
+    // fzf substitutes the quoted value for {2}
+    if let Some(preview) = source.preview_command() {
+        cmd.arg("--preview").arg(preview.replace("{}", "{2}"));
+    }
+
+    if let Some(query) = &options.query {
+        cmd.arg("--query").arg(query);
+    }
+    if options.multi {
+        cmd.arg("--multi");
+    }
+    if let Some(prompt) = &options.prompt {
+        cmd.arg("--prompt").arg(prompt);
+    }
+    if let Some(header) = &options.header {
+        cmd.arg("--header").arg(header);
+    }
+    match options.layout {
+        Some(Layout::Default) => {
+            cmd.arg("--layout=default");
+        }
+        Some(Layout::Reverse) => {
+            cmd.arg("--layout=reverse");
+        }
+        None => {}
+    }
+    if options.sort == Some(false) {
+        cmd.arg("--no-sort");
+    }
+    if !options.expect.is_empty() {
+        cmd.arg(format!("--expect={}", options.expect.join(",")));
+    }
+}
+
+/// Read everything from a pipe of the selector on a background thread
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Run a selector command on the candidates of the source and return the selected ones
+///
+/// With `expect`, the first line of the output is the key that accepted the selection,
+/// empty for Enter, as printed by fzf's `--expect`.
+fn run_command(
+    mut cmd: Command,
+    name: &str,
+    source: &dyn Source,
+    format: LineFormat,
+    exit_codes: &ExitCodes,
+    expect: bool,
+) -> Result<Selection> {
+    // Create a child process for the selector
+    let mut child = cmd
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(error::AnyframeError::IoError)?;
+
+    let Some(stdin) = child.stdin.take() else {
+        return Err(error::AnyframeError::IoError(std::io::Error::other(
+            format!("Failed to open stdin for {}", name),
+        )));
+    };
+    let stdout_reader = read_pipe(child.stdout.take());
+    let stderr_reader = read_pipe(child.stderr.take());
+
+    // Stream the source into the selector's stdin while it is already interactive
+    let mut sink = StdinSink {
+        stdin,
+        format,
+        candidates: Vec::new(),
+    };
+    let streamed = source.stream(&mut sink);
+    // Close stdin so that the selector sees the end of the input
+    let StdinSink {
+        stdin, candidates, ..
+    } = sink;
+    drop(stdin);
+
+    match streamed {
+        // The selector exited before the source finished, e.g. the user already selected
+        Err(error::AnyframeError::IoError(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok(()) => {}
+    }
+
+    // Wait for the selector to finish and get output
+    let status = child.wait().map_err(error::AnyframeError::IoError)?;
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if !status.success() {
+        let code = status.code();
+        if code.is_some() && code == exit_codes.cancelled && stdout.is_empty() {
+            return Err(error::AnyframeError::Cancelled);
+        }
+        if code.is_some() && code == exit_codes.no_match {
+            return Err(error::AnyframeError::NoMatch);
+        }
+
+        return Err(error::AnyframeError::command_failed(&cmd, status, &stderr));
+    }
+
+    let selected = String::from_utf8(stdout).map_err(|e| {
+        error::AnyframeError::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid UTF-8 in {} output: {}", name, e),
+        ))
+    })?;
+
+    let mut lines = selected.lines();
+    let key = if expect {
+        lines.next().filter(|key| !key.is_empty())
+    } else {
+        None
+    };
+
+    // One selected candidate per line, each selected at most once
+    let mut indices: Vec<usize> = Vec::new();
+    for line in lines {
+        if let Some(index) = format.parse(line, &candidates) {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        return Err(error::AnyframeError::NoMatch);
+    }
+
+    let selection = Selection::new(
+        indices
+            .into_iter()
+            .map(|index| candidates[index].clone())
+            .collect(),
+    );
+    Ok(match key {
+        Some(key) => selection.key(key),
+        None => selection,
+    })
 }
 
 /// Peco selector
+///
+/// Peco always allows selecting several lines with Ctrl-Space.
 pub struct Peco {
     path: String,
+    args: Vec<String>,
 }
 
 impl Peco {
@@ -26,88 +480,60 @@ impl Peco {
     pub fn new(path: Option<String>) -> Self {
         Self {
             path: path.unwrap_or_else(|| "peco".to_string()),
+            args: Vec::new(),
         }
     }
+
+    /// Set extra command line arguments passed to peco
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
 }
 
 impl Selector for Peco {
-    fn select(&self, input: &str, query: Option<&str>) -> Result<String> {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
         let mut cmd = Command::new(&self.path);
+        cmd.args(&self.args);
 
-        if let Some(q) = query {
-            cmd.arg("--query").arg(q);
+        if let Some(query) = &options.query {
+            cmd.arg("--query").arg(query);
         }
-
-        // Create a child process for peco
-        let mut child = cmd
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(error::AnyframeError::IoError)?;
-
-        // Write input to peco's stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(input.as_bytes())
-                .map_err(error::AnyframeError::IoError)?;
-        } else {
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to open stdin for peco",
-            )));
+        if let Some(prompt) = &options.prompt {
+            cmd.arg("--prompt").arg(prompt);
         }
-
-        // Wait for peco to finish and get output
-        let output = child
-            .wait_with_output()
-            .map_err(error::AnyframeError::IoError)?;
-
-        if !output.status.success() {
-            // Check if the error is due to user cancellation (peco returns 1 when cancelled)
-            if output.status.code() == Some(1) && output.stdout.is_empty() {
-                return Err(error::AnyframeError::SelectorNotFound(
-                    "Selection cancelled by user".to_string(),
-                ));
+        match options.layout {
+            Some(Layout::Default) => {
+                cmd.args(["--layout", "bottom-up"]);
             }
-
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "peco command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-            )));
-        }
-
-        // Convert output to string and trim whitespace
-        let selected = String::from_utf8(output.stdout)
-            .map_err(|e| {
-                error::AnyframeError::IoError(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Invalid UTF-8 in peco output: {}", e),
-                ))
-            })?
-            .trim()
-            .to_string();
-
-        if selected.is_empty() {
-            return Err(error::AnyframeError::SelectorNotFound(
-                "No item selected".to_string(),
-            ));
+            Some(Layout::Reverse) => {
+                cmd.args(["--layout", "top-down"]);
+            }
+            None => {}
         }
 
-        Ok(selected)
+        // peco returns 1 when cancelled
+        let exit_codes = ExitCodes {
+            cancelled: Some(1),
+            no_match: None,
+        };
+        run_command(cmd, "peco", source, LineFormat::Display, &exit_codes, false)
     }
 
     fn name(&self) -> &'static str {
         "peco"
     }
+
+    fn program(&self) -> Option<&str> {
+        Some(&self.path)
+    }
 }
 
 /// FzfTmux selector
 pub struct FzfTmux {
     path: String,
+    args: Vec<String>,
 }
 
 impl FzfTmux {
@@ -116,88 +542,69 @@ impl FzfTmux {
     pub fn new(path: Option<String>) -> Self {
         Self {
             path: path.unwrap_or_else(|| "fzf-tmux".to_string()),
+            args: Vec::new(),
         }
     }
+
+    /// Set extra command line arguments passed to fzf-tmux
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
 }
 
 impl Selector for FzfTmux {
-    fn select(&self, input: &str, query: Option<&str>) -> Result<String> {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
         let mut cmd = Command::new(&self.path);
-
-        if let Some(q) = query {
-            cmd.arg("--query").arg(q);
-        }
-
-        // Create a child process for fzf-tmux
-        let mut child = cmd
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(error::AnyframeError::IoError)?;
-
-        // Write input to fzf-tmux's stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(input.as_bytes())
-                .map_err(error::AnyframeError::IoError)?;
-        } else {
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to open stdin for fzf-tmux",
-            )));
-        }
-
-        // Wait for fzf-tmux to finish and get output
-        let output = child
-            .wait_with_output()
-            .map_err(error::AnyframeError::IoError)?;
-
-        if !output.status.success() {
-            // Check if the error is due to user cancellation (fzf-tmux returns 130 when cancelled with Ctrl-C)
-            if output.status.code() == Some(130) && output.stdout.is_empty() {
-                return Err(error::AnyframeError::SelectorNotFound(
-                    "Selection cancelled by user".to_string(),
-                ));
+        cmd.args(&self.args);
+
+        // fzf-tmux opens a popup, or a pane below the current one with the given height;
+        // outside tmux, it runs fzf inline
+        match &options.popup {
+            #[cfg(unix)]
+            Some(popup) if tmux::in_tmux() => {
+                cmd.arg("-p");
+                tmux::add_popup_args(&mut cmd, popup);
+            }
+            _ => {
+                if let Some(height) = &options.height {
+                    cmd.arg("-d").arg(height);
+                }
             }
-
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "fzf-tmux command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-            )));
-        }
-
-        // Convert output to string and trim whitespace
-        let selected = String::from_utf8(output.stdout)
-            .map_err(|e| {
-                error::AnyframeError::IoError(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Invalid UTF-8 in fzf-tmux output: {}", e),
-                ))
-            })?
-            .trim()
-            .to_string();
-
-        if selected.is_empty() {
-            return Err(error::AnyframeError::SelectorNotFound(
-                "No item selected".to_string(),
-            ));
         }
-
-        Ok(selected)
+        add_fzf_args(&mut cmd, source, options);
+
+        // fzf-tmux returns 1 when nothing matched and 130 when cancelled with Ctrl-C
+        let exit_codes = ExitCodes {
+            cancelled: Some(130),
+            no_match: Some(1),
+        };
+        run_command(
+            cmd,
+            "fzf-tmux",
+            source,
+            LineFormat::Indexed,
+            &exit_codes,
+            !options.expect.is_empty(),
+        )
     }
 
     fn name(&self) -> &'static str {
         "fzf-tmux"
     }
+
+    fn program(&self) -> Option<&str> {
+        Some(&self.path)
+    }
 }
 
 /// Percol selector
+///
+/// Percol always allows marking several lines with Ctrl-Space.
 pub struct Percol {
     path: String,
+    args: Vec<String>,
 }
 
 impl Percol {
@@ -206,83 +613,168 @@ impl Percol {
     pub fn new(path: Option<String>) -> Self {
         Self {
             path: path.unwrap_or_else(|| "percol".to_string()),
+            args: Vec::new(),
         }
     }
+
+    /// Set extra command line arguments passed to percol
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
 }
 
 impl Selector for Percol {
-    fn select(&self, input: &str, query: Option<&str>) -> Result<String> {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
         let mut cmd = Command::new(&self.path);
+        cmd.args(&self.args);
 
-        if let Some(q) = query {
-            cmd.arg("--query").arg(q);
+        if let Some(query) = &options.query {
+            cmd.arg("--query").arg(query);
+        }
+        if let Some(prompt) = &options.prompt {
+            cmd.arg("--prompt").arg(prompt);
         }
 
-        // Create a child process for percol
-        let mut child = cmd
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(error::AnyframeError::IoError)?;
+        // percol returns 1 when cancelled
+        let exit_codes = ExitCodes {
+            cancelled: Some(1),
+            no_match: None,
+        };
+        run_command(
+            cmd,
+            "percol",
+            source,
+            LineFormat::Display,
+            &exit_codes,
+            false,
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "percol"
+    }
+
+    fn program(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+/// Fzf selector
+pub struct Fzf {
+    path: String,
+    args: Vec<String>,
+}
 
-        // Write input to percol's stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(input.as_bytes())
-                .map_err(error::AnyframeError::IoError)?;
-        } else {
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to open stdin for percol",
-            )));
+impl Fzf {
+    /// Create a new Fzf selector
+    #[must_use]
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path: path.unwrap_or_else(|| "fzf".to_string()),
+            args: Vec::new(),
         }
+    }
 
-        // Wait for percol to finish and get output
-        let output = child
-            .wait_with_output()
-            .map_err(error::AnyframeError::IoError)?;
+    /// Set extra command line arguments passed to fzf
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+}
 
-        if !output.status.success() {
-            // Check if the error is due to user cancellation (percol returns 1 when cancelled)
-            if output.status.code() == Some(1) && output.stdout.is_empty() {
-                return Err(error::AnyframeError::SelectorNotFound(
-                    "Selection cancelled by user".to_string(),
-                ));
-            }
+impl Selector for Fzf {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(&self.args);
 
-            return Err(error::AnyframeError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "percol command failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-            )));
+        if let Some(height) = &options.height {
+            cmd.arg("--height").arg(height);
         }
+        add_fzf_args(&mut cmd, source, options);
+
+        // fzf returns 1 when nothing matched and 130 when interrupted with Ctrl-C or Esc
+        let exit_codes = ExitCodes {
+            cancelled: Some(130),
+            no_match: Some(1),
+        };
+        run_command(
+            cmd,
+            "fzf",
+            source,
+            LineFormat::Indexed,
+            &exit_codes,
+            !options.expect.is_empty(),
+        )
+    }
 
-        // Convert output to string and trim whitespace
-        let selected = String::from_utf8(output.stdout)
-            .map_err(|e| {
-                error::AnyframeError::IoError(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Invalid UTF-8 in percol output: {}", e),
-                ))
-            })?
-            .trim()
-            .to_string();
-
-        if selected.is_empty() {
-            return Err(error::AnyframeError::SelectorNotFound(
-                "No item selected".to_string(),
-            ));
+    fn name(&self) -> &'static str {
+        "fzf"
+    }
+
+    fn program(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+/// Skim selector
+///
+/// skim (`sk`) takes the same options as fzf.
+pub struct Skim {
+    path: String,
+    args: Vec<String>,
+}
+
+impl Skim {
+    /// Create a new Skim selector
+    #[must_use]
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path: path.unwrap_or_else(|| "sk".to_string()),
+            args: Vec::new(),
         }
+    }
+
+    /// Set extra command line arguments passed to sk
+    #[must_use]
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+}
+
+impl Selector for Skim {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(&self.args);
 
-        Ok(selected)
+        if let Some(height) = &options.height {
+            cmd.arg("--height").arg(height);
+        }
+        add_fzf_args(&mut cmd, source, options);
+
+        // sk returns 1 when nothing was selected and 130 when aborted
+        let exit_codes = ExitCodes {
+            cancelled: Some(130),
+            no_match: Some(1),
+        };
+        run_command(
+            cmd,
+            "sk",
+            source,
+            LineFormat::Indexed,
+            &exit_codes,
+            !options.expect.is_empty(),
+        )
     }
 
     fn name(&self) -> &'static str {
-        "percol"
+        "sk"
     }
-}
 
-// Similar implementation for Fzf to be added
+    fn program(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
@@ -0,0 +1,861 @@
+//! Native selector
+//!
+//! A fuzzy finder drawn by anyframe-rs itself on `/dev/tty`, so that the widgets work on
+//! systems where none of the external selectors is installed.
+
+use super::{Layout, SelectOptions, Selection, Selector};
+use crate::{
+    actions::shell_quote,
+    error,
+    matching::{self, Pattern, Ranked},
+    sources::{Candidate, CandidateSink, Source},
+    Result,
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthChar;
+
+/// Prompt shown when none is given
+const DEFAULT_PROMPT: &str = "> ";
+
+/// Time spent reading streamed candidates before the screen is redrawn
+const FEED_BUDGET: Duration = Duration::from_millis(20);
+
+/// Narrowest terminal split to show the preview pane
+const MIN_PREVIEW_WIDTH: usize = 40;
+
+/// Time waited for a key press before reading streamed candidates again
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+/// Native selector
+///
+/// Matches the query like fzf's extended search mode, with the [`matching`] module: the
+/// characters of each space-separated term must appear in order, `'`, `^`, `$` and `!`
+/// select exact, prefix, suffix and inverse terms, and a term is case-sensitive only
+/// when it contains an uppercase letter.
+///
+/// Keys:
+///
+/// - `Enter`: accept the current candidate, or the marked ones in multi-select mode
+/// - `Esc`, `Ctrl-C`, `Ctrl-G`, `Ctrl-Q`: cancel
+/// - `Up`/`Ctrl-P`/`Ctrl-K`, `Down`/`Ctrl-N`/`Ctrl-J`, `PageUp`, `PageDown`: move
+/// - `Tab`, `Shift-Tab`: mark the current candidate and move down or up (multi-select)
+/// - `Left`/`Ctrl-B`, `Right`/`Ctrl-F`, `Home`/`Ctrl-A`, `End`/`Ctrl-E`: move in the query
+/// - `Backspace`/`Ctrl-H`, `Delete`/`Ctrl-D`, `Ctrl-U`, `Ctrl-W`: edit the query
+///
+/// Expected keys accept the selection and take precedence over these bindings.
+///
+/// The prompt is at the top unless [`Layout::Default`] is requested. The height option is
+/// ignored, as the finder always uses the whole terminal.
+pub struct Native {
+    prompt: String,
+}
+
+impl Native {
+    /// Create a new Native selector
+    ///
+    /// The path is ignored, as the native selector does not run an external program.
+    #[must_use]
+    pub fn new(_path: Option<String>) -> Self {
+        Self {
+            prompt: DEFAULT_PROMPT.to_string(),
+        }
+    }
+
+    /// Set extra command line arguments
+    ///
+    /// Only `--prompt <prompt>` is supported, overridden by [`SelectOptions::prompt`].
+    /// Other arguments are rejected.
+    pub fn with_args(mut self, args: Vec<String>) -> Result<Self> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), args.next()) {
+                ("--prompt", Some(prompt)) => self.prompt = prompt,
+                _ => {
+                    return Err(error::AnyframeError::ConfigError(format!(
+                    "native selector does not support the argument '{}' (only --prompt <prompt>)",
+                    arg
+                )))
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl Selector for Native {
+    fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+        let terminal = Terminal::open()?;
+        let finder = Finder::new(&self.prompt, options, source.preview_command());
+
+        // The source is not Send, so the finder runs on its own thread while the source
+        // streams into it from this one
+        let (sender, receiver) = mpsc::channel();
+        let ui = std::thread::spawn(move || finder.run(terminal, &receiver));
+
+        let mut sink = ChannelSink { sender };
+        let streamed = source.stream(&mut sink);
+        let ChannelSink { sender } = sink;
+        let streamed = match streamed {
+            // The finder exited before the source finished, e.g. the user already selected
+            Err(error::AnyframeError::IoError(e)) if e.kind() == io::ErrorKind::BrokenPipe => {
+                Ok(())
+            }
+            Err(e) => {
+                let _ = sender.send(Feed::Failed);
+                Err(e)
+            }
+            Ok(()) => Ok(()),
+        };
+        // Tell the finder that the source has finished
+        drop(sender);
+
+        let outcome = ui.join().map_err(|_| {
+            error::AnyframeError::IoError(io::Error::other("native selector panicked"))
+        })??;
+        streamed?;
+
+        match outcome {
+            Outcome::Accepted(selection) if !selection.items.is_empty() => Ok(selection),
+            Outcome::Accepted(_) => Err(error::AnyframeError::NoMatch),
+            Outcome::Cancelled => Err(error::AnyframeError::Cancelled),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "native"
+    }
+}
+
+/// Message sent from the source to the finder
+enum Feed {
+    /// A candidate was produced
+    Candidate(Candidate),
+    /// The source failed and the finder should stop
+    Failed,
+}
+
+/// Sink sending streamed candidates to the finder's thread
+struct ChannelSink {
+    sender: Sender<Feed>,
+}
+
+impl CandidateSink for ChannelSink {
+    fn push(&mut self, candidate: Candidate) -> Result<()> {
+        self.sender
+            .send(Feed::Candidate(candidate))
+            .map_err(|_| error::AnyframeError::IoError(io::ErrorKind::BrokenPipe.into()))
+    }
+}
+
+/// How the user left the finder
+enum Outcome {
+    /// The candidates were selected
+    Accepted(Selection),
+    /// The selection was cancelled
+    Cancelled,
+}
+
+/// Terminal in raw mode on the alternate screen, restored when dropped
+struct Terminal {
+    tty: BufWriter<File>,
+}
+
+impl Terminal {
+    /// Take over `/dev/tty`
+    ///
+    /// The standard output is left alone, as the widgets capture it.
+    fn open() -> Result<Self> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|e| {
+                error::AnyframeError::IoError(io::Error::new(
+                    e.kind(),
+                    format!("native selector needs a terminal: {}", e),
+                ))
+            })?;
+
+        terminal::enable_raw_mode()?;
+        let mut terminal = Self {
+            tty: BufWriter::new(tty),
+        };
+        queue!(terminal.tty, terminal::EnterAlternateScreen)?;
+        terminal.tty.flush()?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = queue!(self.tty, terminal::LeaveAlternateScreen, cursor::Show);
+        let _ = self.tty.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Order of the matches
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Order {
+    /// Best score first
+    Score,
+    /// Order of the source
+    Source,
+}
+
+/// State of the finder
+struct Finder {
+    prompt: String,
+    /// Lines shown between the information line and the matches
+    header: Vec<String>,
+    /// Arrangement of the prompt and the matches, [`Layout::Default`] listing upwards
+    layout: Layout,
+    order: Order,
+    query: Vec<char>,
+    /// Position of the cursor in the query, in characters
+    cursor: usize,
+    pattern: Pattern,
+    multi: bool,
+    /// Keys accepting the selection besides Enter
+    expect: Vec<String>,
+    candidates: Vec<Candidate>,
+    /// Matching candidates, or `None` when the query is empty and all candidates match
+    matches: Option<Vec<Ranked>>,
+    sorted: bool,
+    /// Position of the current candidate in the matches
+    current: usize,
+    /// Position of the first match shown
+    offset: usize,
+    /// Number of matches shown at once, as of the last redraw
+    page: usize,
+    /// Marked candidates, in the order they were marked
+    selected: Vec<usize>,
+    loading: bool,
+    /// Shell command previewing a candidate, with `{}` standing for its quoted value
+    preview_command: Option<String>,
+    /// Candidate index and lines of the preview shown
+    preview: Option<(usize, Vec<String>)>,
+    /// Candidate index of the preview being computed
+    preview_pending: Option<usize>,
+    preview_sender: Sender<(usize, String)>,
+    preview_receiver: Receiver<(usize, String)>,
+}
+
+impl Finder {
+    fn new(prompt: &str, options: &SelectOptions, preview_command: Option<String>) -> Self {
+        let query: Vec<char> = options.query.as_deref().unwrap_or("").chars().collect();
+        let (preview_sender, preview_receiver) = mpsc::channel();
+        let mut finder = Self {
+            prompt: options.prompt.as_deref().unwrap_or(prompt).to_string(),
+            header: options
+                .header
+                .as_deref()
+                .map(|header| header.lines().map(str::to_string).collect())
+                .unwrap_or_default(),
+            layout: options.layout.unwrap_or(Layout::Reverse),
+            order: if options.sort == Some(false) {
+                Order::Source
+            } else {
+                Order::Score
+            },
+            cursor: query.len(),
+            query,
+            pattern: Pattern::default(),
+            multi: options.multi,
+            expect: options.expect.clone(),
+            candidates: Vec::new(),
+            matches: None,
+            sorted: true,
+            current: 0,
+            offset: 0,
+            page: 1,
+            selected: Vec::new(),
+            loading: true,
+            preview_command,
+            preview: None,
+            preview_pending: None,
+            preview_sender,
+            preview_receiver,
+        };
+        finder.refilter();
+        finder
+    }
+
+    /// Run the finder until the user accepts or cancels
+    fn run(mut self, mut terminal: Terminal, receiver: &Receiver<Feed>) -> Result<Outcome> {
+        let mut dirty = true;
+        loop {
+            match self.feed(receiver) {
+                Some(changed) => dirty |= changed,
+                None => return Ok(Outcome::Cancelled),
+            }
+            dirty |= self.update_preview();
+
+            if dirty {
+                self.draw(&mut terminal.tty)?;
+                dirty = false;
+            }
+
+            if event::poll(POLL_INTERVAL)? {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        if let Some(outcome) = self.handle_key(key) {
+                            return Ok(outcome);
+                        }
+                        dirty = true;
+                    }
+                    Event::Resize(..) => dirty = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Read the candidates streamed so far, returning whether anything changed
+    ///
+    /// Returns `None` when the source failed.
+    fn feed(&mut self, receiver: &Receiver<Feed>) -> Option<bool> {
+        if !self.loading {
+            return Some(false);
+        }
+
+        let started = Instant::now();
+        let mut changed = false;
+        while started.elapsed() < FEED_BUDGET {
+            match receiver.try_recv() {
+                Ok(Feed::Candidate(candidate)) => {
+                    self.push(candidate);
+                    changed = true;
+                }
+                Ok(Feed::Failed) => return None,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.loading = false;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+        Some(changed)
+    }
+
+    /// Receive a computed preview and request the preview of the current candidate,
+    /// returning whether the preview shown changed
+    fn update_preview(&mut self) -> bool {
+        let Some(command) = &self.preview_command else {
+            return false;
+        };
+
+        let mut changed = false;
+        while let Ok((index, text)) = self.preview_receiver.try_recv() {
+            self.preview = Some((index, preview_lines(&text)));
+            self.preview_pending = None;
+            changed = true;
+        }
+
+        // Compute one preview at a time, for the candidate that is current once it is done
+        let current = self.matched(self.current).map(|(index, _)| index);
+        let shown = self.preview.as_ref().map(|(index, _)| *index);
+        if let (Some(index), None) = (current, self.preview_pending) {
+            if shown != Some(index) {
+                let command = command.replace("{}", &shell_quote(&self.candidates[index].value));
+                let sender = self.preview_sender.clone();
+                self.preview_pending = Some(index);
+                std::thread::spawn(move || {
+                    let text = match Command::new("sh")
+                        .arg("-c")
+                        .arg(&command)
+                        .stdin(Stdio::null())
+                        .output()
+                    {
+                        Ok(output) => {
+                            String::from_utf8_lossy(&output.stdout).into_owned()
+                                + &String::from_utf8_lossy(&output.stderr)
+                        }
+                        Err(e) => e.to_string(),
+                    };
+                    let _ = sender.send((index, text));
+                });
+            }
+        }
+        changed
+    }
+
+    /// Add a streamed candidate, or a header line below the header of the options
+    fn push(&mut self, candidate: Candidate) {
+        if candidate.header {
+            self.header.push(candidate.display);
+            return;
+        }
+        let index = self.candidates.len();
+        if let Some(matches) = &mut self.matches {
+            if let Some(matched) = self.pattern.matches(&candidate.display) {
+                matches.push(Ranked::new(index, matched));
+                self.sorted = false;
+            }
+        }
+        self.candidates.push(candidate);
+    }
+
+    /// Match all candidates against the query again
+    fn refilter(&mut self) {
+        self.pattern = Pattern::parse(&self.query.iter().collect::<String>());
+
+        self.matches = if self.pattern.is_empty() {
+            None
+        } else {
+            Some(
+                self.candidates
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, candidate)| {
+                        self.pattern
+                            .matches(&candidate.display)
+                            .map(|matched| Ranked::new(index, matched))
+                    })
+                    .collect(),
+            )
+        };
+        self.sorted = false;
+        self.current = 0;
+        self.offset = 0;
+    }
+
+    /// Sort the matches by score, keeping the order of the source for equal scores
+    ///
+    /// Without sorting, the matches stay in the order of the source.
+    fn sort(&mut self) {
+        if let Some(matches) = &mut self.matches {
+            if !self.sorted && self.order == Order::Score {
+                matching::sort_ranked(matches);
+            }
+        }
+        self.sorted = true;
+    }
+
+    fn match_count(&self) -> usize {
+        self.matches
+            .as_ref()
+            .map_or(self.candidates.len(), Vec::len)
+    }
+
+    /// Get the candidate index and the matched positions of a match
+    fn matched(&self, position: usize) -> Option<(usize, &[usize])> {
+        match &self.matches {
+            Some(matches) => matches
+                .get(position)
+                .map(|m| (m.index, m.positions.as_slice())),
+            None => (position < self.candidates.len()).then_some((position, &[][..])),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Outcome> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let query_length = self.query.len();
+
+        if let Some(name) = key_name(&key).filter(|name| self.expect.contains(name)) {
+            return Some(self.accept(Some(name)));
+        }
+
+        match key.code {
+            KeyCode::Enter => return Some(self.accept(None)),
+            KeyCode::Esc => return Some(Outcome::Cancelled),
+            KeyCode::Char('c' | 'g' | 'q') if ctrl => return Some(Outcome::Cancelled),
+            KeyCode::Up => self.move_up(1),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_up(1),
+            KeyCode::Down => self.move_down(1),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_down(1),
+            KeyCode::PageUp => self.move_up(self.page),
+            KeyCode::PageDown => self.move_down(self.page),
+            KeyCode::Tab if self.multi => {
+                self.toggle();
+                self.move_down(1);
+            }
+            KeyCode::BackTab if self.multi => {
+                self.toggle();
+                self.move_up(1);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('b') if ctrl => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(query_length),
+            KeyCode::Char('f') if ctrl => self.cursor = (self.cursor + 1).min(query_length),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = query_length,
+            KeyCode::Char('e') if ctrl => self.cursor = query_length,
+            KeyCode::Backspace => self.delete_before(1),
+            KeyCode::Char('h') if ctrl => self.delete_before(1),
+            KeyCode::Delete => self.delete_at(),
+            KeyCode::Char('d') if ctrl => self.delete_at(),
+            KeyCode::Char('u') if ctrl => self.delete_before(self.cursor),
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.query.insert(self.cursor, c);
+                self.cursor += 1;
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn accept(&self, key: Option<String>) -> Outcome {
+        let items = if self.multi && !self.selected.is_empty() {
+            self.selected
+                .iter()
+                .map(|&index| self.candidates[index].clone())
+                .collect()
+        } else {
+            self.matched(self.current)
+                .map(|(index, _)| self.candidates[index].clone())
+                .into_iter()
+                .collect()
+        };
+
+        let selection = Selection::new(items);
+        Outcome::Accepted(match key {
+            Some(key) => selection.key(key),
+            None => selection,
+        })
+    }
+
+    fn move_up(&mut self, lines: usize) {
+        self.current = self.current.saturating_sub(lines);
+    }
+
+    fn move_down(&mut self, lines: usize) {
+        self.current = (self.current + lines).min(self.match_count().saturating_sub(1));
+    }
+
+    /// Mark or unmark the current candidate
+    fn toggle(&mut self) {
+        let Some((index, _)) = self.matched(self.current) else {
+            return;
+        };
+        if let Some(position) = self.selected.iter().position(|&i| i == index) {
+            self.selected.remove(position);
+        } else {
+            self.selected.push(index);
+        }
+    }
+
+    fn delete_before(&mut self, count: usize) {
+        let start = self.cursor.saturating_sub(count);
+        if start < self.cursor {
+            self.query.drain(start..self.cursor);
+            self.cursor = start;
+            self.refilter();
+        }
+    }
+
+    fn delete_at(&mut self) {
+        if self.cursor < self.query.len() {
+            self.query.remove(self.cursor);
+            self.refilter();
+        }
+    }
+
+    /// Delete the word before the cursor, like `Ctrl-W` in a shell
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.query[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.query[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.delete_before(self.cursor - start);
+    }
+
+    /// Get the terminal row of a line of the finder, counted from the prompt
+    ///
+    /// Line 0 is the prompt, line 1 the information line, followed by the header and
+    /// the matches.
+    fn row(&self, line: usize, height: usize) -> u16 {
+        if self.layout == Layout::Default {
+            to_u16(height.saturating_sub(line + 1))
+        } else {
+            to_u16(line)
+        }
+    }
+
+    /// Redraw the whole finder: the prompt, an information line, the header, the matches
+    /// and the preview
+    fn draw(&mut self, tty: &mut impl Write) -> io::Result<()> {
+        self.sort();
+
+        let (width, height) = terminal::size()?;
+        let (width, height) = (usize::from(width), usize::from(height));
+        let first_match_line = 2 + self.header.len();
+        self.page = height.saturating_sub(first_match_line).max(1);
+
+        // Split the screen with the preview pane when the terminal is wide enough
+        let list_width = if self.preview_command.is_some() && width >= MIN_PREVIEW_WIDTH {
+            width / 2
+        } else {
+            width
+        };
+        let preview = self
+            .preview
+            .as_ref()
+            .filter(|(index, _)| Some(*index) == self.matched(self.current).map(|(i, _)| i))
+            .map_or(&[][..], |(_, lines)| lines.as_slice());
+
+        // Keep the current candidate visible
+        self.current = self.current.min(self.match_count().saturating_sub(1));
+        if self.current < self.offset {
+            self.offset = self.current;
+        } else if self.current >= self.offset + self.page {
+            self.offset = self.current + 1 - self.page;
+        }
+
+        queue!(tty, cursor::Hide)?;
+        for (line, header) in self.header.iter().enumerate() {
+            // The header reads from top to bottom in both layouts
+            let line = if self.layout == Layout::Default {
+                first_match_line - 1 - line
+            } else {
+                line + 2
+            };
+            queue!(
+                tty,
+                cursor::MoveTo(0, self.row(line, height)),
+                SetForegroundColor(Color::DarkGrey),
+                Print(truncate(header, width)),
+                SetForegroundColor(Color::Reset),
+                terminal::Clear(ClearType::UntilNewLine),
+            )?;
+        }
+
+        for row in 0..self.page {
+            queue!(
+                tty,
+                cursor::MoveTo(0, self.row(first_match_line + row, height))
+            )?;
+            let position = self.offset + row;
+            if let Some((index, positions)) = self.matched(position) {
+                draw_candidate(
+                    tty,
+                    &self.candidates[index].display,
+                    positions,
+                    position == self.current,
+                    self.selected.contains(&index),
+                    list_width,
+                )?;
+            } else {
+                queue!(tty, Print(" ".repeat(list_width)))?;
+            }
+
+            if list_width < width {
+                // The preview reads from top to bottom in both layouts
+                let preview_row = if self.layout == Layout::Default {
+                    self.page - 1 - row
+                } else {
+                    row
+                };
+                let line = preview.get(preview_row).map_or("", String::as_str);
+                queue!(
+                    tty,
+                    SetForegroundColor(Color::DarkGrey),
+                    Print('│'),
+                    SetForegroundColor(Color::Reset),
+                    Print(' '),
+                    Print(truncate(line, width - list_width - 2)),
+                )?;
+            }
+            queue!(tty, terminal::Clear(ClearType::UntilNewLine))?;
+        }
+
+        self.draw_status(tty, width, height)?;
+        tty.flush()
+    }
+
+    /// Draw the information line and the prompt, leaving the terminal cursor in the query
+    fn draw_status(&self, tty: &mut impl Write, width: usize, height: usize) -> io::Result<()> {
+        let selected = if self.selected.is_empty() {
+            String::new()
+        } else {
+            format!(" ({} selected)", self.selected.len())
+        };
+        let info = format!(
+            "  {}/{}{}{}",
+            self.match_count(),
+            self.candidates.len(),
+            selected,
+            if self.loading { " (loading)" } else { "" }
+        );
+        queue!(
+            tty,
+            cursor::MoveTo(0, self.row(1, height)),
+            SetForegroundColor(Color::DarkGrey),
+            Print(truncate(&info, width)),
+            SetForegroundColor(Color::Reset),
+            terminal::Clear(ClearType::UntilNewLine),
+        )?;
+
+        // Draw the prompt last so that the terminal cursor ends up in the query
+        let query: String = self.query.iter().collect();
+        let before_cursor: String = self.query[..self.cursor].iter().collect();
+        let prompt_row = self.row(0, height);
+        queue!(
+            tty,
+            cursor::MoveTo(0, prompt_row),
+            SetForegroundColor(Color::Blue),
+            Print(&self.prompt),
+            SetForegroundColor(Color::Reset),
+            Print(truncate(
+                &query,
+                width.saturating_sub(text_width(&self.prompt))
+            )),
+            terminal::Clear(ClearType::UntilNewLine),
+            cursor::MoveTo(
+                to_u16(text_width(&self.prompt) + text_width(&before_cursor)),
+                prompt_row
+            ),
+            cursor::Show,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Get the name of a key as expected by [`SelectOptions::expect`], such as `ctrl-o`
+fn key_name(key: &KeyEvent) -> Option<String> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(format!("ctrl-{}", c.to_ascii_lowercase()))
+        }
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => Some(format!("alt-{}", c)),
+        KeyCode::F(number) => Some(format!("f{}", number)),
+        _ => None,
+    }
+}
+
+/// Draw a candidate on the current line, highlighting the matched characters
+fn draw_candidate(
+    tty: &mut impl Write,
+    display: &str,
+    positions: &[usize],
+    current: bool,
+    selected: bool,
+    width: usize,
+) -> io::Result<()> {
+    if current {
+        queue!(
+            tty,
+            SetBackgroundColor(Color::DarkGrey),
+            SetAttribute(Attribute::Bold)
+        )?;
+    }
+    queue!(
+        tty,
+        Print(if current { '>' } else { ' ' }),
+        SetForegroundColor(Color::Magenta),
+        Print(if selected { '*' } else { ' ' }),
+        SetForegroundColor(Color::Reset),
+    )?;
+
+    let mut used = 2;
+    let mut positions = positions.iter().peekable();
+    for (i, c) in display.chars().enumerate() {
+        let highlighted = positions.next_if_eq(&&i).is_some();
+
+        // Expand tabs and hide control characters
+        let (text, text_width) = match c {
+            '\t' => {
+                let spaces = 8 - (used - 2) % 8;
+                (" ".repeat(spaces), spaces)
+            }
+            c if c.is_control() => (" ".to_string(), 1),
+            c => (c.to_string(), c.width().unwrap_or(0)),
+        };
+        if used + text_width > width {
+            break;
+        }
+        used += text_width;
+
+        if highlighted {
+            queue!(
+                tty,
+                SetForegroundColor(Color::Green),
+                Print(text),
+                SetForegroundColor(Color::Reset)
+            )?;
+        } else {
+            queue!(tty, Print(text))?;
+        }
+    }
+
+    // Fill the rest of the line, with the background color for the current candidate
+    queue!(
+        tty,
+        Print(" ".repeat(width.saturating_sub(used))),
+        SetAttribute(Attribute::Reset),
+        SetBackgroundColor(Color::Reset)
+    )?;
+    Ok(())
+}
+
+/// Split a preview into lines, removing escape sequences and expanding tabs
+fn preview_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| {
+            let mut cleaned = String::new();
+            let mut column = 0;
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    // Skip CSI sequences such as colors, and the character after other escapes
+                    '\x1b' => {
+                        if chars.next() == Some('[') {
+                            while chars.next().is_some_and(|c| !('@'..='~').contains(&c)) {}
+                        }
+                    }
+                    '\t' => {
+                        let spaces = 8 - column % 8;
+                        cleaned.push_str(&" ".repeat(spaces));
+                        column += spaces;
+                    }
+                    c if c.is_control() => {}
+                    c => {
+                        cleaned.push(c);
+                        column += c.width().unwrap_or(0);
+                    }
+                }
+            }
+            cleaned
+        })
+        .collect()
+}
+
+/// Get the number of columns a text takes on the terminal
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// Cut a text to fit in the given number of columns
+fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+/// Convert a terminal coordinate, saturating at the largest terminal size
+fn to_u16(value: usize) -> u16 {
+    u16::try_from(value).unwrap_or(u16::MAX)
+}
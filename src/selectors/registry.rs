@@ -0,0 +1,467 @@
+//! Selector registry
+//!
+//! Maps selector names (as used by `--selector`, `ANYFRAME_SELECTOR` and the
+//! `:anyframe:selector:` zstyle) to constructors of boxed selectors.
+
+#[cfg(unix)]
+use super::tmux;
+#[cfg(unix)]
+use super::Native;
+use super::{Fzf, FzfTmux, Peco, Percol, Selector, Skim};
+use crate::{error, Result};
+use std::path::{Path, PathBuf};
+
+/// Selectors tried in order when no selector is configured
+#[cfg(unix)]
+pub const DEFAULT_PRIORITY: &[&str] = &["fzf", "sk", "peco", "percol", "native"];
+
+/// Selectors tried in order when no selector is configured
+///
+/// The `native` selector needs a Unix terminal, so it is not available here.
+#[cfg(not(unix))]
+pub const DEFAULT_PRIORITY: &[&str] = &["fzf", "sk", "peco", "percol"];
+
+/// Constructor for a selector, given an optional program path and extra arguments
+pub type SelectorConstructor =
+    Box<dyn Fn(Option<String>, Vec<String>) -> Result<Box<dyn Selector>>>;
+
+/// Registry of named selectors
+pub struct SelectorRegistry {
+    entries: Vec<(String, SelectorConstructor)>,
+    priority: Vec<String>,
+    /// File remembering the selector found by [`discover`](Self::discover)
+    cache: Option<PathBuf>,
+}
+
+impl SelectorRegistry {
+    /// Create an empty registry
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            priority: DEFAULT_PRIORITY.iter().map(ToString::to_string).collect(),
+            cache: None,
+        }
+    }
+
+    /// Get the default location of the discovery cache:
+    /// `$XDG_CACHE_HOME/anyframe-rs/selector`, or `~/.cache/anyframe-rs/selector`
+    #[must_use]
+    pub fn default_cache_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .map(|dir| dir.join("anyframe-rs").join("selector"))
+    }
+
+    /// Remember the discovered selector in this file across runs
+    ///
+    /// The file is only used while `PATH`, the modification times of its
+    /// directories, the priority list and whether anyframe-rs runs inside tmux are
+    /// unchanged, so installing or removing a selector is noticed.
+    #[must_use]
+    pub fn cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache = Some(path.into());
+        self
+    }
+
+    /// Set the selectors tried in order when no selector is configured
+    #[must_use]
+    pub fn priority(mut self, priority: Vec<String>) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Register a selector constructor under the given name
+    ///
+    /// Registering a name twice replaces the previous constructor. The constructor
+    /// rejects the arguments that the selector does not support.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(Option<String>, Vec<String>) -> Result<Box<dyn Selector>> + 'static,
+    {
+        let constructor: SelectorConstructor = Box::new(constructor);
+        if let Some(entry) = self.entries.iter_mut().find(|(n, _)| n == name) {
+            entry.1 = constructor;
+        } else {
+            self.entries.push((name.to_string(), constructor));
+        }
+    }
+
+    /// Get the names of all registered selectors
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Check whether a selector is registered under the given name
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|(n, _)| n == name)
+    }
+
+    /// Create the selector registered under the given name
+    pub fn create(
+        &self,
+        name: &str,
+        path: Option<String>,
+        args: Vec<String>,
+    ) -> Result<Box<dyn Selector>> {
+        let (_, constructor) = self
+            .entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| self.not_found(name))?;
+
+        constructor(path, args)
+    }
+
+    /// Find the first selector of the priority list whose program is installed
+    ///
+    /// Selectors without an external program, such as `native`, are always available.
+    /// `fzf-tmux` is skipped outside tmux. With a [cache](Self::cache), the result of a
+    /// previous run is reused as long as its program is still found.
+    pub fn discover(&self) -> Result<String> {
+        #[cfg(unix)]
+        let in_tmux = tmux::in_tmux();
+        #[cfg(not(unix))]
+        let in_tmux = false;
+
+        let key = self.cache.as_ref().map(|_| self.cache_key(in_tmux));
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            if let Some(name) = read_cache(cache, key) {
+                if self.is_available(&name) {
+                    return Ok(name);
+                }
+            }
+        }
+
+        let name = self.probe(in_tmux)?;
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            // The cache only saves time, so failing to write it is not an error
+            let _ = write_cache(cache, key, &name);
+        }
+        Ok(name)
+    }
+
+    /// Check whether a selector is registered and its program, if any, is installed
+    fn is_available(&self, name: &str) -> bool {
+        self.create(name, None, Vec::new())
+            .is_ok_and(|selector| selector.program().is_none_or(|p| find_program(p).is_some()))
+    }
+
+    /// Describe what the discovery depends on, to tell whether a cached result is valid
+    fn cache_key(&self, in_tmux: bool) -> String {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut lines = vec![
+            format!("priority={}", self.priority.join(",")),
+            format!("tmux={}", in_tmux),
+        ];
+        lines.extend(std::env::split_paths(&path).map(|dir| {
+            let modified = dir
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or_else(|| "-".to_string(), |time| format!("{:?}", time));
+            format!("path={} {}", dir.display(), modified)
+        }));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Find the first installed selector of the priority list
+    fn probe(&self, in_tmux: bool) -> Result<String> {
+        let mut searched = Vec::new();
+        for name in &self.priority {
+            if name == "fzf-tmux" && !in_tmux {
+                continue;
+            }
+
+            let selector = self.create(name, None, Vec::new())?;
+            let found = match selector.program() {
+                Some(program) => {
+                    searched.push(program.to_string());
+                    find_program(program).is_some()
+                }
+                None => true,
+            };
+            if found {
+                return Ok(name.clone());
+            }
+        }
+
+        Err(error::AnyframeError::SelectorNotFound(format!(
+            "No selector found: searched {} in PATH ({}); {}",
+            if searched.is_empty() {
+                "nothing".to_string()
+            } else {
+                searched.join(", ")
+            },
+            search_path(),
+            if cfg!(unix) {
+                "install one or add \"native\" to the priority list"
+            } else {
+                "install one"
+            }
+        )))
+    }
+
+    /// Create a selector from a name and/or a command line such as `fzf --exact`
+    ///
+    /// When no name is given, it is inferred from the program of the command line.
+    /// When neither is given, the selector is [discovered](Self::discover). The program
+    /// of an external selector must exist.
+    pub fn resolve(&self, name: Option<&str>, command: Option<&str>) -> Result<Box<dyn Selector>> {
+        self.resolve_with_args(name, command, &[])
+    }
+
+    /// Create a selector like [`resolve`](Self::resolve), appending extra arguments
+    pub fn resolve_with_args(
+        &self,
+        name: Option<&str>,
+        command: Option<&str>,
+        extra_args: &[String],
+    ) -> Result<Box<dyn Selector>> {
+        let Some(command) = command.filter(|c| !c.trim().is_empty()) else {
+            return self.create_installed(name, None, extra_args.to_vec());
+        };
+
+        let mut words = shell_words::split(command).map_err(|e| {
+            error::AnyframeError::SelectorNotFound(format!(
+                "Invalid selector command '{}': {}",
+                command, e
+            ))
+        })?;
+        if words.is_empty() {
+            return self.create_installed(name, None, extra_args.to_vec());
+        }
+        let program = words.remove(0);
+        words.extend_from_slice(extra_args);
+
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => Path::new(&program)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+
+        self.create_installed(Some(&name), Some(program), words)
+    }
+
+    /// Create a selector, discovering it when no name is given, and check that its
+    /// program exists
+    fn create_installed(
+        &self,
+        name: Option<&str>,
+        path: Option<String>,
+        args: Vec<String>,
+    ) -> Result<Box<dyn Selector>> {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => self.discover()?,
+        };
+        let selector = self.create(&name, path, args)?;
+
+        if let Some(program) = selector.program() {
+            if find_program(program).is_none() {
+                let searched = if program.contains('/') {
+                    format!("{} is not an executable file", program)
+                } else {
+                    format!("searched {} in PATH ({})", program, search_path())
+                };
+                return Err(error::AnyframeError::SelectorNotFound(format!(
+                    "'{}' is not installed: {}",
+                    name, searched
+                )));
+            }
+        }
+        Ok(selector)
+    }
+
+    fn not_found(&self, name: &str) -> error::AnyframeError {
+        error::AnyframeError::SelectorNotFound(format!(
+            "'{}' (available: {})",
+            name,
+            self.names().join(", ")
+        ))
+    }
+}
+
+/// Find an executable program, given by path or by name in `PATH`
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.chars().any(std::path::is_separator) {
+        return executable(PathBuf::from(program));
+    }
+
+    std::env::var_os("PATH")
+        .and_then(|path| std::env::split_paths(&path).find_map(|dir| executable(dir.join(program))))
+}
+
+/// Return the path if it is an executable file
+#[cfg(unix)]
+fn executable(path: PathBuf) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .then_some(path)
+}
+
+/// Return the path, or the path with one of the `PATHEXT` extensions, that is a file
+///
+/// Files have no executable bit outside Unix, so a program is found by extension,
+/// as with `fzf` resolving to `fzf.exe`.
+#[cfg(not(unix))]
+fn executable(path: PathBuf) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path);
+    }
+    if path.extension().is_some() {
+        return None;
+    }
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    extensions
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(|extension| path.with_extension(extension.trim_start_matches('.')))
+        .find(|path| path.is_file())
+}
+
+/// Read the selector remembered in a cache file, if it was found with the same key
+fn read_cache(path: &Path, key: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let name = contents
+        .strip_prefix(key)?
+        .strip_prefix("selector=")?
+        .trim_end();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Remember the discovered selector in a cache file
+///
+/// The file is replaced atomically, so concurrent runs never read a partial file.
+fn write_cache(path: &Path, key: &str, name: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    std::fs::write(&temporary, format!("{}selector={}\n", key, name))?;
+    std::fs::rename(&temporary, path)
+}
+
+/// Get `PATH` for error messages
+fn search_path() -> String {
+    std::env::var("PATH").unwrap_or_default()
+}
+
+impl Default for SelectorRegistry {
+    /// Create a registry containing all built-in selectors
+    ///
+    /// The `native` selector reads the keys from `/dev/tty`, so it is only registered on
+    /// Unix.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("peco", |path, args| {
+            Ok(Box::new(Peco::new(path).with_args(args)))
+        });
+        registry.register("percol", |path, args| {
+            Ok(Box::new(Percol::new(path).with_args(args)))
+        });
+        registry.register("fzf", |path, args| {
+            Ok(Box::new(Fzf::new(path).with_args(args)))
+        });
+        registry.register("fzf-tmux", |path, args| {
+            Ok(Box::new(FzfTmux::new(path).with_args(args)))
+        });
+        registry.register("sk", |path, args| {
+            Ok(Box::new(Skim::new(path).with_args(args)))
+        });
+        #[cfg(unix)]
+        registry.register("native", |path, args| {
+            Ok(Box::new(Native::new(path).with_args(args)?))
+        });
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Build a registry that prefers a missing selector over an installed one and
+    /// counts how often the missing one is probed
+    fn registry(cache: &Path, probes: &Rc<Cell<usize>>) -> Result<SelectorRegistry> {
+        let installed = std::env::current_exe()?.to_string_lossy().into_owned();
+        let probes = Rc::clone(probes);
+        let mut registry = SelectorRegistry::default()
+            .priority(vec!["missing".to_string(), "installed".to_string()])
+            .cache(cache);
+        registry.register("missing", move |_, args| {
+            probes.set(probes.get() + 1);
+            Ok(Box::new(
+                Peco::new(Some("anyframe-rs-missing-selector".to_string())).with_args(args),
+            ))
+        });
+        registry.register("installed", move |_, args| {
+            Ok(Box::new(Peco::new(Some(installed.clone())).with_args(args)))
+        });
+        Ok(registry)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn native_rejects_unsupported_arguments() -> Result<()> {
+        let registry = SelectorRegistry::default();
+        assert_eq!(
+            registry
+                .resolve(None, Some("native --foo"))
+                .err()
+                .map(|e| e.to_string()),
+            Some(
+                "Config error: native selector does not support the argument '--foo' \
+                 (only --prompt <prompt>)"
+                    .to_string()
+            )
+        );
+        assert!(registry.resolve(None, Some("native --prompt")).is_err());
+        registry.resolve(None, Some("native --prompt 'pick> '"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn discover_reuses_the_cached_selector() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = dir.path().join("anyframe-rs").join("selector");
+        let probes = Rc::new(Cell::new(0));
+
+        assert_eq!(registry(&cache, &probes)?.discover()?, "installed");
+        assert_eq!(probes.get(), 1);
+        assert!(cache.exists());
+
+        assert_eq!(registry(&cache, &probes)?.discover()?, "installed");
+        assert_eq!(probes.get(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn discover_probes_again_when_the_priority_changes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = dir.path().join("selector");
+        let probes = Rc::new(Cell::new(0));
+
+        registry(&cache, &probes)?.discover()?;
+        let reordered = registry(&cache, &probes)?.priority(vec![
+            "missing".to_string(),
+            "installed".to_string(),
+            "native".to_string(),
+        ]);
+        assert_eq!(reordered.discover()?, "installed");
+        assert_eq!(probes.get(), 2);
+        Ok(())
+    }
+}
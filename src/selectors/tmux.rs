@@ -0,0 +1,206 @@
+//! tmux popups
+//!
+//! fzf-tmux opens its own popup. Any other selector, including the native one, is shown
+//! in a popup by running anyframe-rs itself again in `tmux display-popup`: the popup
+//! gets a terminal of its own but not the pipes of the caller, so the output of the
+//! inner process is written to files that the outer one reads back.
+
+use super::Popup;
+use crate::{actions::shell_quote, error, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable telling anyframe-rs that it already runs in a popup, set to the
+/// PID of the anyframe-rs process that opened the popup
+pub const POPUP_ENV: &str = "ANYFRAME_POPUP";
+
+/// Environment variables forwarded to the popup, which otherwise gets the environment
+/// of the tmux server instead of the caller's
+const FORWARDED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "SHELL",
+    "HISTFILE",
+    "ZDOTDIR",
+    "fish_history",
+    "XDG_CONFIG_HOME",
+    "XDG_DATA_HOME",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+];
+
+/// Prefix of the environment variables of anyframe-rs, all forwarded to the popup
+const ANYFRAME_ENV_PREFIX: &str = "ANYFRAME_";
+
+/// Check whether anyframe-rs runs inside tmux
+#[must_use]
+pub fn in_tmux() -> bool {
+    std::env::var_os("TMUX").is_some_and(|tmux| !tmux.is_empty())
+}
+
+/// Check whether anyframe-rs runs in a popup opened by [`run_in_popup`]
+#[must_use]
+pub fn in_popup() -> bool {
+    std::env::var_os(POPUP_ENV).is_some()
+}
+
+/// Get the PID of the anyframe-rs process that opened the popup this one runs in
+///
+/// That process and the tmux client it started wait for the popup to close, so the
+/// process list leaves them out.
+#[must_use]
+pub fn popup_caller() -> Option<u32> {
+    std::env::var(POPUP_ENV).ok()?.parse().ok()
+}
+
+/// Output of a process run in a popup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PopupOutput {
+    /// Exit code of the process
+    pub code: u8,
+    /// Standard output of the process
+    pub stdout: Vec<u8>,
+    /// Standard error output of the process
+    pub stderr: Vec<u8>,
+}
+
+/// Run a program in a tmux popup and wait for it to finish
+///
+/// The program runs in the current directory with the caller's `PATH`, history and
+/// anyframe-rs settings. Closing the popup without the program finishing is reported
+/// as [`Cancelled`](error::AnyframeError::Cancelled).
+pub fn run_in_popup(popup: &Popup, program: &Path, args: &[String]) -> Result<PopupOutput> {
+    let dir = TempDir::create()?;
+    let stdout_path = dir.path.join("stdout");
+    let stderr_path = dir.path.join("stderr");
+    let code_path = dir.path.join("code");
+
+    let mut env = vec![format!("{}={}", POPUP_ENV, std::process::id())];
+    for (name, value) in std::env::vars() {
+        if name == POPUP_ENV {
+            continue;
+        }
+        if FORWARDED_ENV.contains(&name.as_str()) || name.starts_with(ANYFRAME_ENV_PREFIX) {
+            env.push(shell_quote(&format!("{}={}", name, value)));
+        }
+    }
+    let command: Vec<String> = std::iter::once(program.to_string_lossy().into_owned())
+        .chain(args.iter().cloned())
+        .map(|word| shell_quote(&word))
+        .collect();
+    let cwd = std::env::current_dir()?;
+    let script = format!(
+        "cd {} && env {} {} >{} 2>{}; echo $? >{}",
+        shell_quote(&cwd.to_string_lossy()),
+        env.join(" "),
+        command.join(" "),
+        shell_quote(&stdout_path.to_string_lossy()),
+        shell_quote(&stderr_path.to_string_lossy()),
+        shell_quote(&code_path.to_string_lossy()),
+    );
+
+    // The popup runs its command with the user's shell, which may not be POSIX
+    let mut tmux = Command::new("tmux");
+    tmux.args(["display-popup", "-E"]);
+    add_popup_args(&mut tmux, popup);
+    tmux.arg(format!("sh -c {}", shell_quote(&script)));
+
+    let output = tmux.output()?;
+    if !output.status.success() {
+        return Err(error::AnyframeError::command_failed(
+            &tmux,
+            output.status,
+            &output.stderr,
+        ));
+    }
+
+    let code = std::fs::read_to_string(&code_path)
+        .ok()
+        .and_then(|code| code.trim().parse().ok())
+        .ok_or(error::AnyframeError::Cancelled)?;
+    Ok(PopupOutput {
+        code,
+        stdout: std::fs::read(&stdout_path)?,
+        stderr: std::fs::read(&stderr_path)?,
+    })
+}
+
+/// Add the size and position of a popup, as taken by both `tmux display-popup` and
+/// `fzf-tmux -p`
+pub(super) fn add_popup_args(cmd: &mut Command, popup: &Popup) {
+    for (flag, value) in [
+        ("-w", &popup.width),
+        ("-h", &popup.height),
+        ("-x", &popup.x),
+        ("-y", &popup.y),
+    ] {
+        if let Some(value) = value {
+            cmd.arg(flag).arg(value);
+        }
+    }
+}
+
+/// Number of names tried for a temporary directory before giving up
+const TEMP_DIR_ATTEMPTS: u32 = 16;
+
+/// Private temporary directory, removed when dropped
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a new directory readable only by the user, with a random name
+    ///
+    /// The directory is created exclusively, so a name taken by another process, or a
+    /// file planted by another user, is never reused: another name is tried instead.
+    fn create() -> Result<Self> {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+        use std::os::unix::fs::DirBuilderExt;
+
+        let mut attempt = 0;
+        loop {
+            // Every RandomState has its own keys, randomly seeded for each process
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(std::process::id());
+            hasher.write_u32(attempt);
+            let path = std::env::temp_dir().join(format!("anyframe-rs-{:016x}", hasher.finish()));
+            match std::fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(err)
+                    if err.kind() == std::io::ErrorKind::AlreadyExists
+                        && attempt + 1 < TEMP_DIR_ATTEMPTS =>
+                {
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn temp_dirs_are_private_unique_and_removed() -> Result<()> {
+        let first = TempDir::create()?;
+        let second = TempDir::create()?;
+        assert_ne!(first.path, second.path);
+        assert_eq!(first.path.metadata()?.permissions().mode() & 0o777, 0o700);
+
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+//! Directory walker
+//!
+//! Lists the entries below a directory recursively, skipping what `.gitignore`,
+//! `.ignore` and the other ignore files of the tree exclude, like fd and ripgrep.
+
+use super::{preview_callback, preview_path, Candidate, CandidateSink, Source};
+use crate::{error, Result};
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Entries listed by the directory source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entries {
+    /// Files and directories
+    All,
+    /// Directories only
+    Directories,
+}
+
+/// Directory source
+///
+/// Streams the files and directories below the root (the current directory by default),
+/// in the order of their names. Each value is the path of the entry, relative to the
+/// current directory when walking it. Directories are shown with a
+/// trailing `/` unless only directories are listed.
+///
+/// Hidden entries are skipped, symbolic links are not followed and ignore files are
+/// respected unless configured otherwise. `.git` directories are skipped along with
+/// ignored entries. Entries that cannot be read, such as
+/// directories without permission, are skipped.
+#[derive(Debug, Clone)]
+pub struct Directory {
+    root: Option<PathBuf>,
+    max_depth: Option<usize>,
+    hidden: bool,
+    follow_symlinks: bool,
+    ignore_files: bool,
+    entries: Entries,
+}
+
+impl Directory {
+    /// Create a new Directory source walking `root`, or the current directory
+    #[must_use]
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            max_depth: None,
+            hidden: false,
+            follow_symlinks: false,
+            ignore_files: true,
+            entries: Entries::All,
+        }
+    }
+
+    /// Only descend this many levels below the root (1 lists the root's entries)
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Include hidden entries, whose name starts with a dot
+    #[must_use]
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Follow symbolic links to directories
+    #[must_use]
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Respect `.ignore` files, and `.gitignore` files and git excludes inside git
+    /// repositories (enabled by default)
+    #[must_use]
+    pub fn with_ignore_files(mut self, ignore_files: bool) -> Self {
+        self.ignore_files = ignore_files;
+        self
+    }
+
+    /// Only list directories
+    #[must_use]
+    pub fn with_dirs_only(mut self, dirs_only: bool) -> Self {
+        self.entries = if dirs_only {
+            Entries::Directories
+        } else {
+            Entries::All
+        };
+        self
+    }
+}
+
+impl Default for Directory {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Source for Directory {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        let root = self.root.as_deref().unwrap_or_else(|| Path::new("."));
+        if !root.is_dir() {
+            return Err(error::AnyframeError::SourceError(format!(
+                "Failed to read directory: {} is not a directory",
+                root.display()
+            )));
+        }
+
+        let walker = walk_builder(root, self.hidden, self.ignore_files)
+            .max_depth(self.max_depth)
+            .follow_links(self.follow_symlinks)
+            .build();
+
+        // The root itself is the first entry
+        for entry in walker.skip(1).filter_map(std::result::Result::ok) {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            if self.entries == Entries::Directories && !is_dir {
+                continue;
+            }
+
+            let path = entry.path();
+            let path = if self.root.is_none() {
+                path.strip_prefix(".").unwrap_or(path)
+            } else {
+                path
+            };
+            let value = path.to_string_lossy();
+            let candidate = if is_dir && self.entries == Entries::All {
+                Candidate::with_display(format!("{}/", value), value.as_ref())
+            } else {
+                Candidate::new(value.as_ref())
+            };
+            sink.push(candidate)?;
+        }
+
+        Ok(())
+    }
+
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        preview_path(Path::new(value)).map(Some)
+    }
+
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
+    }
+
+    fn name(&self) -> &'static str {
+        "directory"
+    }
+}
+
+/// Create a walker of a directory tree, sorted by name
+///
+/// Unless `hidden` is set, hidden entries are skipped. With `ignore_files`, the entries
+/// excluded by ignore files and `.git` directories are skipped.
+pub(super) fn walk_builder(root: &Path, hidden: bool, ignore_files: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!hidden)
+        .ignore(ignore_files)
+        .git_ignore(ignore_files)
+        .git_global(ignore_files)
+        .git_exclude(ignore_files)
+        .parents(ignore_files)
+        .sort_by_file_name(std::cmp::Ord::cmp)
+        // Like ignore files, skip the repository data of git, shown with hidden entries
+        .filter_entry(move |entry| !(ignore_files && entry.file_name() == ".git"));
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Create a tree with nested, hidden and ignored directories
+    fn tree() -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        for path in ["a/b/c", ".hidden", "skipped"] {
+            fs::create_dir_all(dir.path().join(path))?;
+        }
+        for (path, contents) in [
+            (".ignore", "skipped/\n"),
+            (".hidden/x.txt", ""),
+            ("a/file.txt", ""),
+            ("a/b/c/deep.txt", ""),
+            ("skipped/y.txt", ""),
+        ] {
+            fs::write(dir.path().join(path), contents)?;
+        }
+        Ok(dir)
+    }
+
+    /// List the displays of the entries below a directory, relative to it
+    fn list(dir: &Path, directory: &Directory) -> Result<Vec<String>> {
+        let dir = dir.to_string_lossy();
+        let mut candidates = Vec::new();
+        directory.stream(&mut candidates)?;
+        Ok(candidates
+            .iter()
+            .map(|candidate| {
+                let display = candidate.display.as_str();
+                let path = display.strip_prefix(dir.as_ref()).unwrap_or(display);
+                path.trim_start_matches(['/', '\\']).replace('\\', "/")
+            })
+            .collect())
+    }
+
+    fn root(dir: &Path) -> Directory {
+        Directory::new(Some(dir.to_path_buf()))
+    }
+
+    #[test]
+    fn skips_hidden_and_ignored_entries() -> Result<()> {
+        let dir = tree()?;
+        assert_eq!(
+            list(dir.path(), &root(dir.path()))?,
+            ["a/", "a/b/", "a/b/c/", "a/b/c/deep.txt", "a/file.txt"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lists_hidden_entries() -> Result<()> {
+        let dir = tree()?;
+        assert_eq!(
+            list(dir.path(), &root(dir.path()).with_hidden(true))?,
+            [
+                ".hidden/",
+                ".hidden/x.txt",
+                ".ignore",
+                "a/",
+                "a/b/",
+                "a/b/c/",
+                "a/b/c/deep.txt",
+                "a/file.txt"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lists_ignored_entries_without_ignore_files() -> Result<()> {
+        let dir = tree()?;
+        assert_eq!(
+            list(dir.path(), &root(dir.path()).with_ignore_files(false))?,
+            [
+                "a/",
+                "a/b/",
+                "a/b/c/",
+                "a/b/c/deep.txt",
+                "a/file.txt",
+                "skipped/",
+                "skipped/y.txt"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stops_at_the_maximum_depth() -> Result<()> {
+        let dir = tree()?;
+        assert_eq!(
+            list(dir.path(), &root(dir.path()).with_max_depth(1))?,
+            ["a/"]
+        );
+        assert_eq!(
+            list(dir.path(), &root(dir.path()).with_max_depth(2))?,
+            ["a/", "a/b/", "a/file.txt"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lists_directories_only() -> Result<()> {
+        let dir = tree()?;
+        let directory = root(dir.path()).with_dirs_only(true);
+        let mut candidates = Vec::new();
+        directory.stream(&mut candidates)?;
+        let values: Vec<PathBuf> = candidates
+            .into_iter()
+            .map(|candidate| PathBuf::from(candidate.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                dir.path().join("a"),
+                dir.path().join("a/b"),
+                dir.path().join("a/b/c")
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symbolic_links_when_asked() -> Result<()> {
+        let dir = tree()?;
+        std::os::unix::fs::symlink(dir.path().join("a/b"), dir.path().join("link"))?;
+        assert_eq!(
+            list(dir.path(), &root(dir.path()).with_max_depth(1))?,
+            ["a/", "link"]
+        );
+        assert_eq!(
+            list(
+                dir.path(),
+                &root(dir.path())
+                    .with_max_depth(2)
+                    .with_follow_symlinks(true)
+            )?,
+            ["a/", "a/b/", "a/file.txt", "link/", "link/c/"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_roots_that_are_not_directories() -> Result<()> {
+        let dir = tree()?;
+        let file = dir.path().join("a/file.txt");
+        assert_eq!(
+            list(dir.path(), &root(&file)).err().map(|e| e.to_string()),
+            Some(format!(
+                "Source error: Failed to read directory: {} is not a directory",
+                file.display()
+            ))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+//! File finder
+//!
+//! Lists the files below the current directory or the root of its git repository, either
+//! by walking the tree or by asking `git ls-files`.
+
+use super::{
+    command_output, directory::walk_builder, preview_callback, preview_path, stream_command,
+    Candidate, CandidateSink, Source,
+};
+use crate::Result;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// Directory whose files are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Root {
+    /// The current directory
+    Current,
+    /// The root of the git repository of the current directory
+    Repository,
+}
+
+/// Files source
+///
+/// Streams the files below the current directory, or below the root of its git
+/// repository. Each value is the path of a file relative to the current directory, such
+/// as `src/main.rs` or `../README.md`.
+///
+/// The tree is walked like the [`Directory`](super::Directory) source, skipping hidden
+/// files and respecting ignore files unless configured otherwise. Inside a git
+/// repository, `git ls-files` can list the tracked and untracked files instead, which is
+/// faster in large repositories; hidden files are then listed too. Outside a repository,
+/// the tree is always walked from the current directory.
+#[derive(Debug, Clone)]
+pub struct Files {
+    /// Directory the files are listed from, instead of the current directory
+    directory: PathBuf,
+    root: Root,
+    list_with_git: bool,
+    hidden: bool,
+    respect_ignore: bool,
+}
+
+impl Files {
+    /// Create a new Files source listing the files below the current directory
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            directory: PathBuf::from("."),
+            root: Root::Current,
+            list_with_git: false,
+            hidden: false,
+            respect_ignore: true,
+        }
+    }
+
+    /// List the files from this directory instead of the current directory
+    ///
+    /// The values are then relative to this directory.
+    #[must_use]
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = directory.into();
+        self
+    }
+
+    /// List the files of the whole git repository instead of the current directory
+    #[must_use]
+    pub fn with_git_root(mut self, git_root: bool) -> Self {
+        self.root = if git_root {
+            Root::Repository
+        } else {
+            Root::Current
+        };
+        self
+    }
+
+    /// Use `git ls-files` inside git repositories instead of walking the tree
+    #[must_use]
+    pub fn with_git_ls_files(mut self, git_ls_files: bool) -> Self {
+        self.list_with_git = git_ls_files;
+        self
+    }
+
+    /// Include hidden files, whose name starts with a dot
+    #[must_use]
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Respect `.gitignore`, `.ignore` and the git excludes (enabled by default)
+    #[must_use]
+    pub fn with_ignore_files(mut self, ignore_files: bool) -> Self {
+        self.respect_ignore = ignore_files;
+        self
+    }
+
+    /// List the files with `git ls-files`, relative to the current directory
+    fn stream_git(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(&self.directory)
+            .args(["ls-files", "-z", "--cached", "--others"]);
+        if self.respect_ignore {
+            cmd.arg("--exclude-standard");
+        }
+        // The top of the repository as a pathspec lists all files, relative to the
+        // current directory nonetheless
+        if self.root == Root::Repository {
+            cmd.args(["--", ":/"]);
+        }
+
+        let mut previous = String::new();
+        stream_command(&mut cmd, "git ls-files", b'\0', |path| {
+            // Files with conflicts are listed once per stage, and deleted files are
+            // still listed until the deletion is staged
+            if path.is_empty() || path == previous || !self.directory.join(path).exists() {
+                return Ok(());
+            }
+            previous = path.to_string();
+            sink.push(Candidate::new(path))
+        })
+    }
+
+    /// Walk the tree below `root`, given relative to the listed directory as `cdup`
+    /// (such as `../../`) along with the listed directory relative to the root
+    fn stream_walk(&self, sink: &mut dyn CandidateSink, cdup: &str, prefix: &str) -> Result<()> {
+        let root = self.directory.join(cdup);
+        let walker = walk_builder(&root, self.hidden, self.respect_ignore).build();
+
+        for entry in walker.filter_map(std::result::Result::ok) {
+            if !entry
+                .file_type()
+                .is_some_and(|t| t.is_file() || t.is_symlink())
+            {
+                continue;
+            }
+            let Ok(path) = entry.path().strip_prefix(&root) else {
+                continue;
+            };
+            let path = relative_to(path, Path::new(prefix));
+            sink.push(Candidate::new(path.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Source for Files {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        if self.root == Root::Current && !self.list_with_git {
+            return self.stream_walk(sink, "", "");
+        }
+
+        // Outside a git repository, or without git, fall back to walking the current
+        // directory
+        let repository = command_output(
+            Command::new("git").current_dir(&self.directory).args([
+                "rev-parse",
+                "--show-cdup",
+                "--show-prefix",
+            ]),
+            "git",
+        );
+        let Ok(repository) = repository else {
+            return self.stream_walk(sink, "", "");
+        };
+
+        if self.list_with_git {
+            return self.stream_git(sink);
+        }
+        let mut lines = repository.lines();
+        let cdup = lines.next().unwrap_or_default();
+        let prefix = lines.next().unwrap_or_default();
+        self.stream_walk(sink, cdup, prefix)
+    }
+
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        preview_path(Path::new(value)).map(Some)
+    }
+
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
+    }
+
+    fn name(&self) -> &'static str {
+        "files"
+    }
+}
+
+/// Make a path relative to a directory, both given relative to the same root
+///
+/// For instance, `src/main.rs` relative to `docs/` is `../src/main.rs`. `..` components
+/// are resolved lexically first.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let (path, base) = (normalize(path), normalize(base));
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+    std::iter::repeat_n(Component::ParentDir, base.len() - common)
+        .chain(path[common..].iter().copied())
+        .collect()
+}
+
+/// Resolve the `.` and `..` components of a relative path without reading the file system
+fn normalize(path: &Path) -> Vec<Component<'_>> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(components.last(), Some(Component::Normal(_))) => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn relative(path: &str, base: &str) -> PathBuf {
+        relative_to(Path::new(path), Path::new(base))
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    /// Create a git repository with a `.gitignore`, a hidden file and an ignored file
+    fn repository() -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("docs"))?;
+        fs::create_dir_all(dir.path().join("src"))?;
+        for (path, contents) in [
+            (".gitignore", "ignored.txt\n"),
+            (".hidden", ""),
+            ("README.md", ""),
+            ("docs/guide.md", ""),
+            ("ignored.txt", ""),
+            ("src/main.rs", ""),
+        ] {
+            fs::write(dir.path().join(path), contents)?;
+        }
+        command_output(
+            Command::new("git")
+                .current_dir(dir.path())
+                .args(["init", "-q"]),
+            "git",
+        )?;
+        Ok(dir)
+    }
+
+    /// List the files from the `src` directory of a repository
+    fn list(dir: &Path, files: Files) -> Result<Vec<PathBuf>> {
+        let mut candidates = Vec::new();
+        files
+            .with_directory(dir.join("src"))
+            .stream(&mut candidates)?;
+        Ok(candidates
+            .into_iter()
+            .map(|candidate| PathBuf::from(candidate.value))
+            .collect())
+    }
+
+    #[test]
+    fn makes_paths_relative_to_a_directory() {
+        assert_eq!(relative("src/main.rs", ""), Path::new("src/main.rs"));
+        assert_eq!(relative("src/main.rs", "src"), Path::new("main.rs"));
+        assert_eq!(relative("src/main.rs", "docs"), Path::new("../src/main.rs"));
+        assert_eq!(relative("src", "src"), Path::new(""));
+    }
+
+    #[test]
+    fn makes_paths_relative_to_a_directory_that_is_not_an_ancestor() {
+        assert_eq!(relative("src/a/b.rs", "src/c/d"), Path::new("../../a/b.rs"));
+        assert_eq!(
+            relative("docs/x.md", "src/bin"),
+            Path::new("../../docs/x.md")
+        );
+    }
+
+    #[test]
+    fn resolves_parent_components() {
+        assert_eq!(
+            relative("../README.md", "src"),
+            Path::new("../../README.md")
+        );
+        assert_eq!(relative("src/../README.md", ""), Path::new("README.md"));
+        assert_eq!(relative("src/main.rs", "docs/.."), Path::new("src/main.rs"));
+        assert_eq!(relative("./src/main.rs", "./src"), Path::new("main.rs"));
+    }
+
+    #[test]
+    fn walks_the_current_directory() -> Result<()> {
+        let dir = repository()?;
+        assert_eq!(list(dir.path(), Files::new())?, paths(&["main.rs"]));
+        Ok(())
+    }
+
+    #[test]
+    fn walks_the_repository() -> Result<()> {
+        let dir = repository()?;
+        let files = Files::new().with_git_root(true);
+        assert_eq!(
+            list(dir.path(), files.clone())?,
+            paths(&["../README.md", "../docs/guide.md", "main.rs"])
+        );
+        assert_eq!(
+            list(dir.path(), files.clone().with_hidden(true))?,
+            paths(&[
+                "../.gitignore",
+                "../.hidden",
+                "../README.md",
+                "../docs/guide.md",
+                "main.rs"
+            ])
+        );
+        assert_eq!(
+            list(dir.path(), files.with_ignore_files(false))?,
+            paths(&[
+                "../README.md",
+                "../docs/guide.md",
+                "../ignored.txt",
+                "main.rs"
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_files_with_git() -> Result<()> {
+        let dir = repository()?;
+        let files = Files::new().with_git_ls_files(true);
+        assert_eq!(list(dir.path(), files.clone())?, paths(&["main.rs"]));
+        assert_eq!(
+            list(dir.path(), files.clone().with_git_root(true))?,
+            paths(&[
+                "../.gitignore",
+                "../.hidden",
+                "../README.md",
+                "../docs/guide.md",
+                "main.rs"
+            ])
+        );
+        assert_eq!(
+            list(
+                dir.path(),
+                files.with_git_root(true).with_ignore_files(false)
+            )?,
+            paths(&[
+                "../.gitignore",
+                "../.hidden",
+                "../README.md",
+                "../docs/guide.md",
+                "../ignored.txt",
+                "main.rs"
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn walks_outside_a_repository() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("src"))?;
+        fs::write(dir.path().join("src/main.rs"), "")?;
+        fs::write(dir.path().join("README.md"), "")?;
+        let files = Files::new().with_git_root(true).with_git_ls_files(true);
+        assert_eq!(list(dir.path(), files)?, paths(&["main.rs"]));
+        Ok(())
+    }
+}
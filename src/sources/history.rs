@@ -0,0 +1,477 @@
+//! Shell history files
+//!
+//! Parses the history files of zsh, bash and fish directly instead of asking a
+//! non-interactive shell, which has no history loaded.
+
+use super::{Candidate, CandidateSink, Source};
+use crate::{error, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Byte used by zsh to escape special bytes in its history file
+const ZSH_META: u8 = 0x83;
+
+/// Entry of a shell history file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Command line, possibly spanning several lines
+    pub command: String,
+    /// Start time in seconds since the epoch
+    pub timestamp: Option<u64>,
+    /// Duration in seconds
+    pub duration: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Create an entry without timing information
+    #[must_use]
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            timestamp: None,
+            duration: None,
+        }
+    }
+
+    /// Convert the entry into a candidate carrying its timing as metadata
+    #[must_use]
+    pub fn into_candidate(self) -> Candidate {
+        let mut candidate = Candidate::new(self.command);
+        if let Some(timestamp) = self.timestamp {
+            candidate = candidate.metadata("timestamp", timestamp.to_string());
+        }
+        if let Some(duration) = self.duration {
+            candidate = candidate.metadata("duration", duration.to_string());
+        }
+        candidate
+    }
+}
+
+/// Undo zsh's metafication of a history file
+///
+/// zsh writes some bytes as `0x83` followed by the original byte XOR `0x20`.
+#[must_use]
+pub fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut unmetafied = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = iter.next() {
+                unmetafied.push(next ^ 0x20);
+            }
+        } else {
+            unmetafied.push(byte);
+        }
+    }
+    unmetafied
+}
+
+/// Parse the contents of a zsh history file, oldest entry first
+///
+/// Both the plain format and the EXTENDED_HISTORY format (`: <start>:<duration>;<command>`)
+/// are supported. A line ending with a backslash continues on the next line.
+#[must_use]
+pub fn parse_zsh(bytes: &[u8]) -> Vec<HistoryEntry> {
+    let contents = String::from_utf8_lossy(&unmetafy(bytes)).into_owned();
+
+    let mut entries = Vec::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        // Join continuation lines, keeping the newline in the command
+        let mut command = line.to_string();
+        while command.ends_with('\\') {
+            let Some(next) = lines.next() else {
+                break;
+            };
+            command.pop();
+            command.push('\n');
+            command.push_str(next);
+        }
+
+        let entry = parse_zsh_extended(&command).unwrap_or_else(|| HistoryEntry::new(command));
+        if !entry.command.trim().is_empty() {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// Parse an entry in the EXTENDED_HISTORY format
+fn parse_zsh_extended(entry: &str) -> Option<HistoryEntry> {
+    let rest = entry.strip_prefix(": ")?;
+    let (timing, command) = rest.split_once(';')?;
+    let (timestamp, duration) = timing.split_once(':')?;
+
+    Some(HistoryEntry {
+        command: command.to_string(),
+        timestamp: Some(timestamp.trim().parse().ok()?),
+        duration: Some(duration.trim().parse().ok()?),
+    })
+}
+
+/// Remove duplicated commands, keeping the most recent entry, newest first
+#[must_use]
+pub fn newest_unique(entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .rev()
+        .filter(|entry| seen.insert(entry.command.clone()))
+        .collect()
+}
+
+/// Parse the contents of a bash history file, oldest entry first
+///
+/// Timestamp comments (`#<epoch>`) written when `HISTTIMEFORMAT` is set apply to the
+/// following command.
+#[must_use]
+pub fn parse_bash(bytes: &[u8]) -> Vec<HistoryEntry> {
+    let contents = String::from_utf8_lossy(bytes);
+
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+    for line in contents.lines() {
+        if let Some(epoch) = line.strip_prefix('#') {
+            if let Ok(epoch) = epoch.trim().parse() {
+                timestamp = Some(epoch);
+                continue;
+            }
+        }
+
+        if !line.trim().is_empty() {
+            entries.push(HistoryEntry {
+                command: line.to_string(),
+                timestamp: timestamp.take(),
+                duration: None,
+            });
+        }
+    }
+    entries
+}
+
+/// Parse the contents of a fish history file, oldest entry first
+///
+/// fish writes a YAML-like list of `- cmd:` entries with `when:` and `paths:` fields.
+/// Paths are not part of [`HistoryEntry`] and are ignored.
+#[must_use]
+pub fn parse_fish(bytes: &[u8]) -> Vec<HistoryEntry> {
+    let contents = String::from_utf8_lossy(bytes);
+
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for line in contents.lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            entries.push(HistoryEntry::new(unescape_fish(command)));
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some(entry) = entries.last_mut() {
+                entry.timestamp = when.trim().parse().ok();
+            }
+        }
+    }
+    entries.retain(|entry| !entry.command.trim().is_empty());
+    entries
+}
+
+/// Undo fish's escaping of backslashes and newlines in history commands
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('\\') | None => unescaped.push('\\'),
+                Some(other) => {
+                    unescaped.push('\\');
+                    unescaped.push(other);
+                }
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// Shell whose history file is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryBackend {
+    /// zsh history file (`$HISTFILE`, `$ZDOTDIR/.zsh_history` or `~/.zsh_history`)
+    Zsh,
+    /// bash history file (`$HISTFILE` or `~/.bash_history`)
+    Bash,
+    /// fish history file (`$XDG_DATA_HOME/fish/fish_history`)
+    Fish,
+}
+
+impl HistoryBackend {
+    /// Detect the backend from the `$SHELL` environment variable, defaulting to zsh
+    #[must_use]
+    pub fn detect() -> Self {
+        let shell = std::env::var_os("SHELL").unwrap_or_default();
+        let shell = Path::new(&shell)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        shell.parse().unwrap_or(Self::Zsh)
+    }
+
+    /// Parse the contents of a history file of this shell, oldest entry first
+    #[must_use]
+    pub fn parse(self, bytes: &[u8]) -> Vec<HistoryEntry> {
+        match self {
+            Self::Zsh => parse_zsh(bytes),
+            Self::Bash => parse_bash(bytes),
+            Self::Fish => parse_fish(bytes),
+        }
+    }
+
+    /// Default location of the history file of this shell
+    #[must_use]
+    pub fn default_file(self) -> Option<PathBuf> {
+        let histfile = std::env::var_os("HISTFILE").filter(|h| !h.is_empty());
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+
+        match self {
+            Self::Zsh => histfile.map(PathBuf::from).or_else(|| {
+                std::env::var_os("ZDOTDIR")
+                    .map(PathBuf::from)
+                    .or(home)
+                    .map(|dir| dir.join(".zsh_history"))
+            }),
+            Self::Bash => histfile
+                .map(PathBuf::from)
+                .or_else(|| home.map(|home| home.join(".bash_history"))),
+            Self::Fish => {
+                // fish keeps one file per session name, "fish" by default
+                let session = std::env::var("fish_history").unwrap_or_else(|_| "fish".to_string());
+                std::env::var_os("XDG_DATA_HOME")
+                    .map(PathBuf::from)
+                    .or_else(|| home.map(|home| home.join(".local/share")))
+                    .map(|dir| dir.join("fish").join(format!("{}_history", session)))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for HistoryBackend {
+    type Err = error::AnyframeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zsh" => Ok(Self::Zsh),
+            "bash" => Ok(Self::Bash),
+            "fish" => Ok(Self::Fish),
+            _ => Err(error::AnyframeError::SourceError(format!(
+                "Unknown history backend '{}' (available: zsh, bash, fish)",
+                s
+            ))),
+        }
+    }
+}
+
+/// History source
+///
+/// Reads the history file of zsh, bash or fish and provides unique commands, most
+/// recent first. Timestamps and durations of the entries are exposed as the
+/// `timestamp` and `duration` metadata.
+#[derive(Debug, Clone)]
+pub struct History {
+    backend: HistoryBackend,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Create a new History source
+    ///
+    /// The backend is detected from `$SHELL`. When no path is given, the default
+    /// history file of the backend is used.
+    #[must_use]
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            backend: HistoryBackend::detect(),
+            path,
+        }
+    }
+
+    /// Set the shell whose history file is read
+    #[must_use]
+    pub fn with_backend(mut self, backend: HistoryBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Read and parse the history file
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>> {
+        let path = self
+            .path
+            .clone()
+            .or_else(|| self.backend.default_file())
+            .ok_or_else(|| {
+                error::AnyframeError::SourceError(
+                    "Failed to locate history file: set HISTFILE".to_string(),
+                )
+            })?;
+
+        let bytes = std::fs::read(&path).map_err(|e| {
+            error::AnyframeError::SourceError(format!(
+                "Failed to read history file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        Ok(newest_unique(self.backend.parse(&bytes)))
+    }
+}
+
+impl Source for History {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        for entry in self.entries()? {
+            sink.push(entry.into_candidate())?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "history"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, timestamp: u64, duration: u64) -> HistoryEntry {
+        HistoryEntry {
+            command: command.to_string(),
+            timestamp: Some(timestamp),
+            duration: Some(duration),
+        }
+    }
+
+    fn timed(command: &str, timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: Some(timestamp),
+            ..HistoryEntry::new(command)
+        }
+    }
+
+    #[test]
+    fn unmetafy_restores_escaped_bytes() {
+        // "ー" is E3 83 BC, whose 0x83 byte zsh writes as 0x83 0xA3
+        assert_eq!(
+            unmetafy(b"echo \xe3\x83\xa3\xbc"),
+            "echo ー".as_bytes().to_vec()
+        );
+        assert_eq!(unmetafy(b"plain"), b"plain".to_vec());
+        // A trailing meta byte has nothing to escape and is dropped
+        assert_eq!(unmetafy(b"ls\x83"), b"ls".to_vec());
+    }
+
+    #[test]
+    fn parse_zsh_reads_extended_history() {
+        let entries = parse_zsh(b": 1700000000:0;git status\n: 1700000005:12;cargo build\n");
+        assert_eq!(
+            entries,
+            vec![
+                entry("git status", 1_700_000_000, 0),
+                entry("cargo build", 1_700_000_005, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_zsh_reads_plain_and_metafied_lines() {
+        let entries = parse_zsh(b"ls -la\n\necho \xe3\x83\xa3\xbc\n");
+        assert_eq!(
+            entries,
+            vec![HistoryEntry::new("ls -la"), HistoryEntry::new("echo ー")]
+        );
+    }
+
+    #[test]
+    fn parse_zsh_keeps_semicolons_of_the_command() {
+        let entries = parse_zsh(b": 1700000000:3;cd /tmp; ls\n");
+        assert_eq!(entries, vec![entry("cd /tmp; ls", 1_700_000_000, 3)]);
+    }
+
+    #[test]
+    fn parse_zsh_joins_continuation_lines() {
+        let entries = parse_zsh(b": 1700000000:0;for f in *; do\\\n  echo $f\\\ndone\nls\n");
+        assert_eq!(
+            entries,
+            vec![
+                entry("for f in *; do\n  echo $f\ndone", 1_700_000_000, 0),
+                HistoryEntry::new("ls"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_zsh_treats_malformed_timing_as_plain_command() {
+        let entries = parse_zsh(b": not-a-time;echo hi\n");
+        assert_eq!(entries, vec![HistoryEntry::new(": not-a-time;echo hi")]);
+    }
+
+    #[test]
+    fn newest_unique_keeps_the_newest_entry() {
+        let entries = newest_unique(vec![
+            entry("ls", 1, 0),
+            entry("git status", 2, 0),
+            entry("ls", 3, 0),
+        ]);
+        assert_eq!(entries, vec![entry("ls", 3, 0), entry("git status", 2, 0)]);
+    }
+
+    #[test]
+    fn parse_bash_applies_timestamps_to_the_next_command() {
+        let entries = parse_bash(b"#1700000000\ngit status\nls\n#1700000009\nmake\n");
+        assert_eq!(
+            entries,
+            vec![
+                timed("git status", 1_700_000_000),
+                HistoryEntry::new("ls"),
+                timed("make", 1_700_000_009),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_bash_keeps_comments_that_are_not_timestamps() {
+        let entries = parse_bash(b"# not a timestamp\n\necho hi\n");
+        assert_eq!(
+            entries,
+            vec![
+                HistoryEntry::new("# not a timestamp"),
+                HistoryEntry::new("echo hi"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fish_reads_commands_and_times() {
+        let history = b"- cmd: git status\n  when: 1700000000\n- cmd: vim README.md\n  when: 1700000042\n  paths:\n    - README.md\n";
+        let entries = parse_fish(history);
+        assert_eq!(
+            entries,
+            vec![
+                timed("git status", 1_700_000_000),
+                timed("vim README.md", 1_700_000_042),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_fish_unescapes_newlines_and_backslashes() {
+        let entries = parse_fish(b"- cmd: echo a\\nb\n- cmd: printf '\\\\t'\n- cmd: echo \\x\n");
+        assert_eq!(
+            entries,
+            vec![
+                HistoryEntry::new("echo a\nb"),
+                HistoryEntry::new("printf '\\t'"),
+                HistoryEntry::new("echo \\x"),
+            ]
+        );
+    }
+}
@@ -2,156 +2,365 @@
 //!
 //! Sources provide data to be filtered, such as command history, directories, processes, etc.
 
-use crate::{error, Result};
-use std::process::Command;
+use crate::{actions::shell_quote, error, Result};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub mod directory;
+pub mod files;
+pub mod history;
+pub mod process;
+
+pub use directory::Directory;
+pub use files::Files;
+pub use history::History;
+pub use process::Process;
+
+/// Item provided by a source
+///
+/// Selectors show the `display` text, while widgets act on the `value`, so a source can
+/// show pretty columns without widgets having to parse them back. Header lines, such as
+/// the column names of a table, are shown but can never be selected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidate {
+    /// Text shown in the selector
+    pub display: String,
+    /// Value passed to actions when the candidate is selected
+    pub value: String,
+    /// Additional named fields, such as a git status or a timestamp
+    pub metadata: BTreeMap<String, String>,
+    /// Whether the candidate is a header line rather than a selectable item
+    pub header: bool,
+}
 
-/// Trait for sources
-pub trait Source {
-    /// Get the data from the source
-    fn get_data(&self) -> Result<String>;
+impl Candidate {
+    /// Create a candidate whose display text is its value
+    #[must_use]
+    pub fn new(value: impl Into<String>) -> Self {
+        let value = value.into();
+        Self {
+            display: value.clone(),
+            value,
+            metadata: BTreeMap::new(),
+            header: false,
+        }
+    }
 
-    /// Get the name of the source
-    fn name(&self) -> &str;
+    /// Create a candidate with a display text different from its value
+    #[must_use]
+    pub fn with_display(display: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            display: display.into(),
+            value: value.into(),
+            metadata: BTreeMap::new(),
+            header: false,
+        }
+    }
+
+    /// Create a header line, which has no value
+    ///
+    /// Sources stream their header lines before the other candidates and count them in
+    /// [`Source::header_lines`].
+    #[must_use]
+    pub fn header_line(display: impl Into<String>) -> Self {
+        Self {
+            display: display.into(),
+            header: true,
+            ..Self::default()
+        }
+    }
+
+    /// Add a metadata field
+    #[must_use]
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
 }
 
-/// History source
-pub struct History;
+/// Receiver of the candidates streamed by a source
+pub trait CandidateSink {
+    /// Receive a candidate
+    ///
+    /// Returning an error stops the source, e.g. when the selector has already exited.
+    fn push(&mut self, candidate: Candidate) -> Result<()>;
+}
 
-impl Source for History {
-    fn get_data(&self) -> Result<String> {
-        // Get command history using zsh
-        let history_output = Command::new("zsh")
-            .arg("-c")
-            .arg("history -n -r 1 | awk '!a[$0]++'")
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute zsh: {}", e))
-            })?;
+impl CandidateSink for Vec<Candidate> {
+    fn push(&mut self, candidate: Candidate) -> Result<()> {
+        Self::push(self, candidate);
+        Ok(())
+    }
+}
 
-        if !history_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "zsh command failed: {}",
-                String::from_utf8_lossy(&history_output.stderr)
-            )));
-        }
+/// Trait for sources
+pub trait Source {
+    /// Stream the candidates into the sink as soon as they are produced
+    ///
+    /// This lets a selector open before the source has finished.
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()>;
+
+    /// Get all candidates from the source
+    fn candidates(&self) -> Result<Vec<Candidate>> {
+        let mut candidates = Vec::new();
+        self.stream(&mut candidates)?;
+        Ok(candidates)
+    }
 
-        let history_str = String::from_utf8(history_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in history output: {}", e))
-        })?;
+    /// Get the number of header lines streamed before the candidates
+    ///
+    /// Header lines are created with [`Candidate::header_line`]. Selectors show them
+    /// above the candidates and never select them: fzf is given `--header-lines`, while
+    /// selectors without such an option receive them as ordinary lines and ignore them
+    /// in their output. The default has none.
+    fn header_lines(&self) -> usize {
+        0
+    }
 
-        Ok(history_str)
+    /// Get the data from the source as display lines
+    fn get_data(&self) -> Result<String> {
+        Ok(self
+            .candidates()?
+            .into_iter()
+            .map(|candidate| candidate.display + "\n")
+            .collect())
     }
 
-    fn name(&self) -> &'static str {
-        "history"
+    /// Preview a candidate given its value, e.g. the log of a git branch
+    ///
+    /// Returns `None` when the source has no preview, which is the default.
+    fn preview(&self, _value: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Get a shell command printing the preview of a candidate
+    ///
+    /// `{}` in the command stands for the shell-quoted value of the candidate.
+    /// Selectors run this command to show a preview pane. The default has none.
+    fn preview_command(&self) -> Option<String> {
+        None
     }
+
+    /// Get the name of the source
+    fn name(&self) -> &str;
 }
 
-/// Directory source
-pub struct Directory;
+impl<T: Source + ?Sized> Source for Box<T> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        (**self).stream(sink)
+    }
 
-impl Source for Directory {
-    fn get_data(&self) -> Result<String> {
-        // Get current directory path
-        let current_dir = std::env::current_dir().map_err(|e| {
-            error::AnyframeError::SourceError(format!("Failed to get current directory: {}", e))
-        })?;
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        (**self).preview(value)
+    }
 
-        // Read directory entries
-        let entries = std::fs::read_dir(&current_dir).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Failed to read directory: {}", e))
-        })?;
+    fn preview_command(&self) -> Option<String> {
+        (**self).preview_command()
+    }
 
-        // Collect file names
-        let mut file_list = String::new();
-        for entry in entries {
-            let entry = entry.map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to read directory entry: {}", e))
-            })?;
+    fn header_lines(&self) -> usize {
+        (**self).header_lines()
+    }
 
-            let file_name = entry.file_name();
-            let file_name_str = file_name.to_string_lossy();
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
 
-            file_list.push_str(&file_name_str);
-            file_list.push('\n');
+/// A fixed list of candidates
+impl Source for Vec<Candidate> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        for candidate in self {
+            sink.push(candidate.clone())?;
         }
+        Ok(())
+    }
 
-        Ok(file_list)
+    fn header_lines(&self) -> usize {
+        self.iter().take_while(|candidate| candidate.header).count()
     }
 
     fn name(&self) -> &'static str {
-        "directory"
+        "candidates"
     }
 }
 
-/// Process source
-pub struct Process;
-
-impl Source for Process {
-    fn get_data(&self) -> Result<String> {
-        // Get process list using ps command
-        let username = std::env::var("USER").map_err(|e| {
-            error::AnyframeError::SourceError(format!(
-                "Failed to get USER environment variable: {}",
-                e
-            ))
+/// Run a command and pass each item of its stdout to `on_item` as soon as it is read
+///
+/// Items are terminated by `separator`. The command is killed when `on_item` fails.
+fn stream_command(
+    cmd: &mut Command,
+    name: &str,
+    separator: u8,
+    mut on_item: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            error::AnyframeError::SourceError(format!("Failed to execute {}: {}", name, e))
         })?;
 
-        let ps_output = Command::new("ps")
-            .arg("-u")
-            .arg(&username)
-            .arg("-o")
-            .arg("pid,stat,%cpu,%mem,cputime,command")
-            .output()
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(error::AnyframeError::SourceError(format!(
+            "Failed to capture {} output",
+            name
+        )));
+    };
+
+    // Drain stderr concurrently so that the command never blocks on a full pipe
+    let stderr_reader = std::thread::spawn(move || {
+        let mut message = String::new();
+        let _ = stderr.read_to_string(&mut message);
+        message
+    });
+
+    let mut result = Ok(());
+    for item in BufReader::new(stdout).split(separator) {
+        result = item
             .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute ps command: {}", e))
-            })?;
-
-        if !ps_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "ps command failed: {}",
-                String::from_utf8_lossy(&ps_output.stderr)
-            )));
+                error::AnyframeError::SourceError(format!("Failed to read {} output: {}", name, e))
+            })
+            .and_then(|item| {
+                String::from_utf8(item).map_err(|e| {
+                    error::AnyframeError::SourceError(format!(
+                        "Invalid UTF-8 in {} output: {}",
+                        name, e
+                    ))
+                })
+            })
+            .and_then(|item| on_item(item.trim_end_matches('\r')));
+        if result.is_err() {
+            let _ = child.kill();
+            break;
         }
+    }
+
+    let status = child.wait().map_err(|e| {
+        error::AnyframeError::SourceError(format!("Failed to wait for {}: {}", name, e))
+    })?;
+    let message = stderr_reader.join().unwrap_or_default();
+    result?;
+
+    if !status.success() {
+        return Err(error::AnyframeError::command_failed(
+            cmd,
+            status,
+            message.as_bytes(),
+        ));
+    }
 
-        let ps_str = String::from_utf8(ps_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in ps output: {}", e))
+    Ok(())
+}
+
+/// Maximum number of lines of a file shown in a preview
+const PREVIEW_LINES: usize = 200;
+
+/// Get a command calling back into this executable to preview a candidate of a source
+///
+/// The source must be known to [`from_spec`].
+fn preview_callback(source: &str) -> Option<String> {
+    let executable = std::env::current_exe().ok()?;
+    Some(format!(
+        "{} preview {} {{}}",
+        shell_quote(&executable.to_string_lossy()),
+        source
+    ))
+}
+
+/// Run a command and return its standard output
+fn command_output(cmd: &mut Command, name: &str) -> Result<String> {
+    let output = cmd.stdin(Stdio::null()).output().map_err(|e| {
+        error::AnyframeError::SourceError(format!("Failed to execute {}: {}", name, e))
+    })?;
+
+    if !output.status.success() {
+        return Err(error::AnyframeError::command_failed(
+            cmd,
+            output.status,
+            &output.stderr,
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Preview a path: the beginning of a file or the entries of a directory
+fn preview_path(path: &Path) -> Result<String> {
+    if path.is_dir() {
+        let entries = std::fs::read_dir(path).map_err(|e| {
+            error::AnyframeError::SourceError(format!(
+                "Failed to read directory {}: {}",
+                path.display(),
+                e
+            ))
         })?;
 
-        Ok(ps_str)
+        let mut names: Vec<String> = entries
+            .filter_map(std::result::Result::ok)
+            .map(|entry| {
+                let mut name = entry.file_name().to_string_lossy().into_owned();
+                if entry.path().is_dir() {
+                    name.push('/');
+                }
+                name
+            })
+            .collect();
+        names.sort();
+        return Ok(names.into_iter().map(|name| name + "\n").collect());
     }
 
-    fn name(&self) -> &'static str {
-        "process"
+    let contents = std::fs::read(path).map_err(|e| {
+        error::AnyframeError::SourceError(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    if contents.contains(&0) {
+        return Ok(format!("{}: binary file\n", path.display()));
     }
+
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .take(PREVIEW_LINES)
+        .map(|line| line.to_string() + "\n")
+        .collect())
 }
 
 /// Ghq repository source
 pub struct GhqRepository;
 
 impl Source for GhqRepository {
-    fn get_data(&self) -> Result<String> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
         // Get ghq repository list using ghq command
-        let ghq_output = Command::new("ghq")
-            .arg("list")
-            .arg("--full-path")
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute ghq command: {}", e))
-            })?;
-
-        if !ghq_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "ghq command failed: {}",
-                String::from_utf8_lossy(&ghq_output.stderr)
-            )));
-        }
+        stream_command(
+            Command::new("ghq").arg("list").arg("--full-path"),
+            "ghq",
+            b'\n',
+            |line| sink.push(Candidate::new(line)),
+        )
+    }
 
-        let ghq_str = String::from_utf8(ghq_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in ghq output: {}", e))
-        })?;
+    /// Show the README of the repository, or its entries when it has none
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        let repository = Path::new(value);
+        let readme = [
+            "README.md",
+            "README",
+            "README.rst",
+            "README.txt",
+            "readme.md",
+        ]
+        .iter()
+        .map(|name| repository.join(name))
+        .find(|path| path.is_file());
+
+        preview_path(readme.as_deref().unwrap_or(repository)).map(Some)
+    }
 
-        Ok(ghq_str)
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
     }
 
     fn name(&self) -> &'static str {
@@ -183,7 +392,7 @@ impl GitBranch {
 }
 
 impl Source for GitBranch {
-    fn get_data(&self) -> Result<String> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
         // Build git branch command options
         let mut args = vec!["branch", "--list", "-v"];
 
@@ -193,49 +402,39 @@ impl Source for GitBranch {
             args.push("-r");
         }
 
-        // Execute git branch command
-        let branch_output = Command::new("git").args(&args).output().map_err(|e| {
-            error::AnyframeError::SourceError(format!("Failed to execute git: {}", e))
-        })?;
-
-        if !branch_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "git command failed: {}",
-                String::from_utf8_lossy(&branch_output.stderr)
-            )));
-        }
-
-        let branch_str = String::from_utf8(branch_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in branch output: {}", e))
-        })?;
+        // Execute git branch command, excluding the current branch unless requested
+        stream_command(Command::new("git").args(&args), "git", b'\n', |line| {
+            if !self.include_current_branch && line.starts_with('*') {
+                return Ok(());
+            }
+
+            // Remove the '*' marker of the current branch and leading whitespace
+            let line = line.strip_prefix('*').unwrap_or(line).trim_start();
+            let branch_name = line.split_whitespace().next().unwrap_or("");
+            sink.push(Candidate::with_display(line, branch_name))
+        })
+    }
 
-        // Process output based on whether to include current branch
-        let processed_output = if self.include_current_branch {
-            // If including current branch, remove '*' marker
-            branch_str
-                .lines()
-                .map(|line| {
-                    if line.starts_with('*') {
-                        // Remove '*' from lines starting with '*'
-                        line.replacen('*', " ", 1).trim_start().to_string()
-                    } else {
-                        // Remove leading whitespace from other lines
-                        line.trim_start().to_string()
-                    }
-                })
-                .collect::<Vec<String>>()
-                .join("\n")
-        } else {
-            // If not including current branch, exclude lines starting with '*'
-            branch_str
-                .lines()
-                .filter(|line| !line.starts_with('*'))
-                .map(|line| line.trim_start().to_string())
-                .collect::<Vec<String>>()
-                .join("\n")
-        };
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        command_output(
+            Command::new("git")
+                .args([
+                    "log",
+                    "--graph",
+                    "--oneline",
+                    "--decorate",
+                    "--color=always",
+                ])
+                .arg("--max-count=100")
+                .arg(value)
+                .arg("--"),
+            "git",
+        )
+        .map(Some)
+    }
 
-        Ok(processed_output)
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
     }
 
     fn name(&self) -> &'static str {
@@ -257,20 +456,20 @@ impl GitStatus {
 }
 
 impl Source for GitStatus {
-    fn get_data(&self) -> Result<String> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
         // Get relative path from git root directory
-        let base_path_output = Command::new("git")
-            .args(["rev-parse", "--show-cdup"])
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute git: {}", e))
-            })?;
+        let mut base_path_command = Command::new("git");
+        base_path_command.args(["rev-parse", "--show-cdup"]);
+        let base_path_output = base_path_command.output().map_err(|e| {
+            error::AnyframeError::SourceError(format!("Failed to execute git: {}", e))
+        })?;
 
         if !base_path_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "git command failed: {}",
-                String::from_utf8_lossy(&base_path_output.stderr)
-            )));
+            return Err(error::AnyframeError::command_failed(
+                &base_path_command,
+                base_path_output.status,
+                &base_path_output.stderr,
+            ));
         }
 
         let base_path = String::from_utf8(base_path_output.stdout).map_err(|e| {
@@ -279,86 +478,225 @@ impl Source for GitStatus {
 
         let base_path = base_path.trim();
 
-        // Execute git status command
-        let status_output = Command::new("git")
-            .args(["status", "--porcelain"])
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute git: {}", e))
-            })?;
-
-        if !status_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "git command failed: {}",
-                String::from_utf8_lossy(&status_output.stderr)
-            )));
-        }
+        // Execute git status command with NUL separated entries to keep paths unquoted
+        let mut is_original_path = false;
+        stream_command(
+            Command::new("git").args(["status", "--porcelain", "-z"]),
+            "git",
+            b'\0',
+            |entry| {
+                // Renames and copies are followed by an entry with the original path
+                if std::mem::take(&mut is_original_path) {
+                    return Ok(());
+                }
+                let Some((status, file_path)) = entry.get(0..2).zip(entry.get(3..)) else {
+                    return Ok(());
+                };
+                is_original_path = status.contains(['R', 'C']);
 
-        let status_str = String::from_utf8(status_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in status output: {}", e))
-        })?;
+                let path = format!("{}{}", base_path, file_path);
+                let display = format!("{}\t{}", status, path);
 
-        // Process status output
-        let processed_output = status_str
-            .lines()
-            .map(|line| {
-                if line.len() >= 3 {
-                    let status = &line[0..2];
-                    let file_path = &line[3..];
-                    format!("{}\t{}{}", status, base_path, file_path)
-                } else {
-                    line.to_string()
-                }
-            })
-            .filter(|line| {
+                // If pattern is specified, include only lines that start with the pattern
                 if let Some(pattern) = &self.pattern {
-                    // If pattern is specified, include only lines that start with the pattern
-                    line.starts_with(pattern)
-                } else {
-                    // If no pattern is specified, include all lines
-                    true
+                    if !display.starts_with(pattern.as_str()) {
+                        return Ok(());
+                    }
                 }
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
 
-        Ok(processed_output)
+                sink.push(Candidate::with_display(display, path).metadata("status", status))
+            },
+        )
+    }
+
+    /// Show the unstaged changes of the file, its staged changes when it has none, and
+    /// its contents when it is untracked
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        for staged in [false, true] {
+            let mut cmd = Command::new("git");
+            cmd.args(["diff", "--color=always"]);
+            if staged {
+                cmd.arg("--cached");
+            }
+            let diff = command_output(cmd.arg("--").arg(value), "git")?;
+            if !diff.is_empty() {
+                return Ok(Some(diff));
+            }
+        }
+
+        preview_path(Path::new(value)).map(Some)
+    }
+
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
     }
 
     fn name(&self) -> &'static str {
         "git-status"
     }
 }
+
 /// Cdr source
 pub struct Cdr;
 
 impl Source for Cdr {
-    fn get_data(&self) -> Result<String> {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
         // Get cdr list using zsh
-        let cdr_output = Command::new("zsh")
-            .arg("-c")
-            .arg("cdr -l | sed 's/^[^ ][^ ]*  *//'")
-            .output()
-            .map_err(|e| {
-                error::AnyframeError::SourceError(format!("Failed to execute cdr: {}", e))
-            })?;
+        stream_command(
+            Command::new("zsh")
+                .arg("-c")
+                .arg("cdr -l | sed 's/^[^ ][^ ]*  *//'"),
+            "cdr",
+            b'\n',
+            |line| sink.push(Candidate::new(line)),
+        )
+    }
 
-        if !cdr_output.status.success() {
-            return Err(error::AnyframeError::SourceError(format!(
-                "cdr command failed: {}",
-                String::from_utf8_lossy(&cdr_output.stderr)
-            )));
+    fn name(&self) -> &'static str {
+        "cdr"
+    }
+}
+
+/// Command output source
+///
+/// Runs a shell command line with `sh -c` and provides each line of its output.
+/// When a field is set, the value of a line is that field (1-based) of the line,
+/// split on whitespace or on the delimiter, while the whole line is displayed.
+pub struct CommandOutput {
+    command: String,
+    field: Option<usize>,
+    delimiter: Option<String>,
+    preview: Option<String>,
+    header_lines: usize,
+}
+
+impl CommandOutput {
+    /// Create a new CommandOutput source
+    #[must_use]
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            field: None,
+            delimiter: None,
+            preview: None,
+            header_lines: 0,
         }
+    }
 
-        let cdr_str = String::from_utf8(cdr_output.stdout).map_err(|e| {
-            error::AnyframeError::SourceError(format!("Invalid UTF-8 in cdr output: {}", e))
-        })?;
+    /// Use a field of each line (1-based) as its value
+    #[must_use]
+    pub fn with_field(mut self, field: usize) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    /// Split lines on a delimiter instead of whitespace when extracting the field
+    #[must_use]
+    pub fn with_delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
 
-        Ok(cdr_str)
+    /// Set a shell command previewing a line, with `{}` standing for its quoted value
+    #[must_use]
+    pub fn with_preview(mut self, preview: impl Into<String>) -> Self {
+        self.preview = Some(preview.into());
+        self
+    }
+
+    /// Show the first lines of the output as headers, such as the column names of a table
+    #[must_use]
+    pub fn with_header_lines(mut self, header_lines: usize) -> Self {
+        self.header_lines = header_lines;
+        self
+    }
+
+    /// Get the value of a line
+    fn value<'a>(&self, line: &'a str) -> &'a str {
+        let Some(field) = self.field else {
+            return line;
+        };
+        let index = field.saturating_sub(1);
+
+        match self.delimiter.as_deref() {
+            Some(delimiter) => line.split(delimiter).nth(index),
+            None => line.split_whitespace().nth(index),
+        }
+        .unwrap_or("")
+    }
+}
+
+impl Source for CommandOutput {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        let mut headers = 0;
+        stream_command(
+            Command::new("sh").arg("-c").arg(&self.command),
+            &self.command,
+            b'\n',
+            |line| {
+                if headers < self.header_lines {
+                    headers += 1;
+                    return sink.push(Candidate::header_line(line));
+                }
+                sink.push(Candidate::with_display(line, self.value(line)))
+            },
+        )
+    }
+
+    fn preview_command(&self) -> Option<String> {
+        self.preview.clone()
+    }
+
+    fn header_lines(&self) -> usize {
+        self.header_lines
     }
 
     fn name(&self) -> &'static str {
-        "cdr"
+        "command"
+    }
+}
+
+/// Names of the sources accepted by [`from_spec`], besides `command:<command line>`
+pub const SOURCE_NAMES: &[&str] = &[
+    "history",
+    "directory",
+    "files",
+    "process",
+    "ghq-repository",
+    "git-branch",
+    "git-status",
+    "cdr",
+];
+
+/// Create a source from a specification
+///
+/// A specification is either the name of a built-in source with its default options,
+/// such as `git-branch`, or `command:<command line>` for the output of a command.
+pub fn from_spec(spec: &str) -> Result<Box<dyn Source>> {
+    if let Some(command) = spec.strip_prefix("command:") {
+        if command.trim().is_empty() {
+            return Err(error::AnyframeError::SourceError(
+                "Empty command in source 'command:'".to_string(),
+            ));
+        }
+        return Ok(Box::new(CommandOutput::new(command.trim())));
     }
+
+    Ok(match spec {
+        "history" => Box::new(History::new(None)),
+        "directory" => Box::new(Directory::default()),
+        "files" => Box::new(Files::default()),
+        "process" => Box::new(Process::new()),
+        "ghq-repository" => Box::new(GhqRepository),
+        "git-branch" => Box::new(GitBranch::new(false, false, false)),
+        "git-status" => Box::new(GitStatus::new(None)),
+        "cdr" => Box::new(Cdr),
+        _ => {
+            return Err(error::AnyframeError::SourceError(format!(
+                "Unknown source '{}' (available: {}, command:<command line>)",
+                spec,
+                SOURCE_NAMES.join(", ")
+            )))
+        }
+    })
 }
-// Similar implementations for other sources to be added
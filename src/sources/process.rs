@@ -0,0 +1,799 @@
+//! Process list
+//!
+//! Reads `/proc/<pid>/stat`, `status` and `cmdline` directly on Linux instead of running
+//! `ps`, whose options vary between systems. Other Unix systems, and Linux without
+//! `/proc`, fall back to the portable options of `ps`. Processes cannot be listed on
+//! other platforms.
+
+#[cfg(unix)]
+use super::command_output;
+use super::{preview_callback, Candidate, CandidateSink, Source};
+use crate::{error, Result};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+#[cfg(target_os = "linux")]
+use std::{fs, path::Path};
+
+/// Directory of the process information on Linux
+#[cfg(target_os = "linux")]
+const PROC: &str = "/proc";
+
+/// Process source
+///
+/// Streams the processes of the current user, ordered by PID, below a header line. The
+/// display shows the PID, the user (with all users), the state, the CPU usage, the
+/// resident memory, the elapsed time and the command line of each process, and the value
+/// is the PID.
+///
+/// The CPU usage is averaged over the lifetime of the process, like `ps`. The processes
+/// belong to the user whose real user ID is that of anyframe-rs, so `$USER` is not
+/// needed. anyframe-rs itself and the processes it started, such as the selector, are
+/// left out, along with the process trees given to [`excluding`](Self::excluding).
+#[derive(Debug, Clone, Default)]
+pub struct Process {
+    all_users: bool,
+    tree: bool,
+    name: Option<String>,
+    pattern: Option<Regex>,
+    excluded: Vec<u32>,
+}
+
+impl Process {
+    /// Create a new Process source listing the processes of the current user
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// List the processes of all users
+    #[must_use]
+    pub fn with_all_users(mut self, all_users: bool) -> Self {
+        self.all_users = all_users;
+        self
+    }
+
+    /// Show each process below its parent, with its command line indented
+    #[must_use]
+    pub fn with_tree(mut self, tree: bool) -> Self {
+        self.tree = tree;
+        self
+    }
+
+    /// Only list the processes with this name, or whose program has this file name
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Only list the processes whose command line matches a regular expression
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            error::AnyframeError::SourceError(format!(
+                "Invalid process pattern '{}': {}",
+                pattern, e
+            ))
+        })?;
+        self.pattern = Some(regex);
+        Ok(self)
+    }
+
+    /// Also leave out a process and the processes it started, such as the anyframe-rs
+    /// process waiting for the tmux popup that this one runs in
+    #[must_use]
+    pub fn excluding(mut self, pid: u32) -> Self {
+        self.excluded.push(pid);
+        self
+    }
+
+    /// Format the columns before the command line, for a process or the header
+    fn columns(&self, [pid, user, state, cpu, rss, elapsed]: [&str; 6]) -> String {
+        let mut columns = format!("{:>7} ", pid);
+        if self.all_users {
+            let _ = write!(columns, "{:<10} ", user);
+        }
+        let _ = write!(
+            columns,
+            "{:<1} {:>5} {:>6} {:>11} ",
+            state, cpu, rss, elapsed
+        );
+        columns
+    }
+
+    /// Check whether a process passes the user, name and pattern filters
+    fn accepts(&self, info: &Info, uid: u32) -> bool {
+        (self.all_users || info.uid == uid)
+            && self.name.as_ref().is_none_or(|name| {
+                info.name == *name || info.program().is_some_and(|program| program == name)
+            })
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&info.command))
+    }
+}
+
+impl Source for Process {
+    fn stream(&self, sink: &mut dyn CandidateSink) -> Result<()> {
+        let processes = snapshot()?;
+        let excluded: HashSet<u32> = std::iter::once(std::process::id())
+            .chain(self.excluded.iter().copied())
+            .flat_map(|pid| descendants(&processes, pid))
+            .collect();
+        let uid = current_uid()?;
+
+        let processes: Vec<Info> = processes
+            .into_iter()
+            .filter(|info| !excluded.contains(&info.pid) && self.accepts(info, uid))
+            .collect();
+        let depths = if self.tree {
+            tree_order(&processes)
+        } else {
+            (0..processes.len()).map(|index| (index, 0)).collect()
+        };
+
+        let header = self.columns(["PID", "USER", "S", "%CPU", "RSS", "ELAPSED"]);
+        sink.push(Candidate::header_line(header + "COMMAND"))?;
+
+        let mut users = HashMap::new();
+        for (index, depth) in depths {
+            let info = &processes[index];
+            let user = if self.all_users {
+                users
+                    .entry(info.uid)
+                    .or_insert_with(|| user_name(info.uid))
+                    .as_str()
+            } else {
+                ""
+            };
+            let mut display = self.columns([
+                &info.pid.to_string(),
+                user,
+                &info.state.to_string(),
+                &format_tenths(info.cpu_tenths),
+                &format_size(info.rss),
+                &format_elapsed(info.elapsed),
+            ]);
+            if depth > 0 {
+                display.push_str(&"   ".repeat(depth - 1));
+                display.push_str(" \\_ ");
+            }
+            display.push_str(&info.command);
+
+            sink.push(
+                Candidate::with_display(display, info.pid.to_string())
+                    .metadata("name", info.name.as_str()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn preview(&self, value: &str) -> Result<Option<String>> {
+        let Ok(pid) = value.parse::<u32>() else {
+            return Ok(None);
+        };
+        let Some(info) = find_process(pid)? else {
+            return Ok(Some(format!("Process {} is not running\n", pid)));
+        };
+
+        let mut preview = String::new();
+        let _ = writeln!(preview, "PID:     {}", info.pid);
+        let _ = writeln!(preview, "PPID:    {}", info.ppid);
+        let _ = writeln!(preview, "User:    {}", user_name(info.uid));
+        let _ = writeln!(preview, "State:   {}", info.state);
+        let _ = writeln!(preview, "CPU:     {}%", format_tenths(info.cpu_tenths));
+        let _ = writeln!(preview, "RSS:     {}", format_size(info.rss));
+        let _ = writeln!(preview, "Elapsed: {}", format_elapsed(info.elapsed));
+        #[cfg(target_os = "linux")]
+        if let Ok(cwd) = fs::read_link(Path::new(PROC).join(pid.to_string()).join("cwd")) {
+            let _ = writeln!(preview, "Cwd:     {}", cwd.display());
+        }
+        let _ = writeln!(preview, "Command: {}", info.command);
+        Ok(Some(preview))
+    }
+
+    fn preview_command(&self) -> Option<String> {
+        preview_callback(self.name())
+    }
+
+    fn header_lines(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "process"
+    }
+}
+
+/// Information about a process
+#[derive(Debug, Clone)]
+struct Info {
+    pid: u32,
+    ppid: u32,
+    /// Real user ID
+    uid: u32,
+    /// State, such as `R` for running or `S` for sleeping
+    state: char,
+    /// Name of the process, as set by the kernel or the process itself
+    name: String,
+    /// Command line, or the name in brackets for kernel threads
+    command: String,
+    /// CPU usage over the lifetime of the process, in tenths of a percent
+    cpu_tenths: u64,
+    /// Resident memory in bytes
+    rss: u64,
+    /// Seconds since the process started
+    elapsed: u64,
+}
+
+impl Info {
+    /// Get the file name of the program of the command line
+    fn program(&self) -> Option<&str> {
+        let program = self.command.split_whitespace().next()?;
+        Some(program.rsplit('/').next().unwrap_or(program))
+    }
+}
+
+/// Clock and memory units of the kernel, for `/proc/<pid>/stat`
+#[cfg(target_os = "linux")]
+struct Clock {
+    /// Clock ticks per second
+    ticks_per_second: u64,
+    /// Bytes per memory page
+    page_size: u64,
+    /// Clock ticks since boot
+    uptime: u64,
+}
+
+#[cfg(target_os = "linux")]
+impl Clock {
+    /// Read the units and the uptime of the system
+    fn new() -> Result<Self> {
+        // SAFETY: sysconf has no preconditions and returns -1 for unknown names
+        let (ticks, page_size) = unsafe {
+            (
+                libc::sysconf(libc::_SC_CLK_TCK),
+                libc::sysconf(libc::_SC_PAGESIZE),
+            )
+        };
+        let ticks_per_second = u64::try_from(ticks).unwrap_or(100).max(1);
+
+        let uptime = fs::read_to_string(Path::new(PROC).join("uptime")).map_err(|e| {
+            error::AnyframeError::SourceError(format!("Failed to read {}/uptime: {}", PROC, e))
+        })?;
+        let hundredths = uptime
+            .split_whitespace()
+            .next()
+            .and_then(|seconds| parse_decimal(seconds, 100))
+            .unwrap_or(0);
+
+        Ok(Self {
+            ticks_per_second,
+            page_size: u64::try_from(page_size).unwrap_or(4096),
+            uptime: hundredths * ticks_per_second / 100,
+        })
+    }
+}
+
+/// Error for the platforms where processes cannot be listed
+#[cfg(not(unix))]
+fn unsupported() -> error::AnyframeError {
+    error::AnyframeError::SourceError(
+        "Listing processes is not supported on this platform".to_string(),
+    )
+}
+
+/// Get the real user ID of anyframe-rs
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn current_uid() -> Result<u32> {
+    // SAFETY: getuid has no preconditions and cannot fail
+    Ok(unsafe { libc::getuid() })
+}
+
+/// Get the real user ID of anyframe-rs
+#[cfg(not(unix))]
+fn current_uid() -> Result<u32> {
+    Err(unsupported())
+}
+
+/// Check whether the process information can be read from `/proc`
+#[cfg(target_os = "linux")]
+fn has_proc() -> bool {
+    Path::new(PROC).join("self/stat").exists()
+}
+
+/// Get all processes, ordered by PID
+#[cfg(unix)]
+fn snapshot() -> Result<Vec<Info>> {
+    #[cfg(target_os = "linux")]
+    if has_proc() {
+        return proc_snapshot();
+    }
+    ps_snapshot()
+}
+
+/// Get all processes, ordered by PID
+#[cfg(not(unix))]
+fn snapshot() -> Result<Vec<Info>> {
+    Err(unsupported())
+}
+
+/// Get a process by PID
+fn find_process(pid: u32) -> Result<Option<Info>> {
+    #[cfg(target_os = "linux")]
+    if has_proc() {
+        return Ok(read_process(pid, &Clock::new()?));
+    }
+    Ok(snapshot()?.into_iter().find(|info| info.pid == pid))
+}
+
+/// Get all processes from `/proc`, ordered by PID
+#[cfg(target_os = "linux")]
+fn proc_snapshot() -> Result<Vec<Info>> {
+    let clock = Clock::new()?;
+    let entries = fs::read_dir(PROC).map_err(|e| {
+        error::AnyframeError::SourceError(format!("Failed to read {}: {}", PROC, e))
+    })?;
+    let mut processes: Vec<Info> = entries
+        .filter_map(std::result::Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        // Processes may exit while they are read
+        .filter_map(|pid| read_process(pid, &clock))
+        .collect();
+    processes.sort_by_key(|info| info.pid);
+    Ok(processes)
+}
+
+/// Read the information of a process from `/proc`
+#[cfg(target_os = "linux")]
+fn read_process(pid: u32, clock: &Clock) -> Option<Info> {
+    let dir = Path::new(PROC).join(pid.to_string());
+    let stat = fs::read_to_string(dir.join("stat")).ok()?;
+    let stat = parse_stat(&stat)?;
+
+    let status = fs::read_to_string(dir.join("status")).ok()?;
+    let uid = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+
+    // Arguments are separated by NUL bytes; kernel threads have none
+    let cmdline = fs::read(dir.join("cmdline")).ok()?;
+    let command = String::from_utf8_lossy(&cmdline)
+        .split('\0')
+        .filter(|arg| !arg.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let command = if command.is_empty() {
+        format!("[{}]", stat.name)
+    } else {
+        command
+    };
+
+    let elapsed_ticks = clock.uptime.saturating_sub(stat.start_ticks);
+    Some(Info {
+        pid,
+        ppid: stat.ppid,
+        uid,
+        state: stat.state,
+        name: stat.name.to_string(),
+        command,
+        cpu_tenths: (stat.cpu_ticks * 1000)
+            .checked_div(elapsed_ticks)
+            .unwrap_or(0),
+        rss: stat.rss_pages * clock.page_size,
+        elapsed: elapsed_ticks / clock.ticks_per_second,
+    })
+}
+
+/// Fields of `/proc/<pid>/stat`
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Eq)]
+struct Stat<'a> {
+    name: &'a str,
+    state: char,
+    ppid: u32,
+    /// Clock ticks spent in user and kernel mode
+    cpu_ticks: u64,
+    /// Clock ticks since boot when the process started
+    start_ticks: u64,
+    /// Resident memory in pages
+    rss_pages: u64,
+}
+
+/// Parse the contents of `/proc/<pid>/stat`
+#[cfg(target_os = "linux")]
+fn parse_stat(stat: &str) -> Option<Stat<'_>> {
+    // The name is in parentheses and may itself contain spaces and parentheses
+    let (open, close) = (stat.find('(')?, stat.rfind(')')?);
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    // Fields are numbered from the state, the third field of stat(5)
+    let field = |index: usize| fields.get(index).and_then(|f| f.parse::<u64>().ok());
+    Some(Stat {
+        name: stat.get(open + 1..close)?,
+        state: fields.first()?.chars().next()?,
+        ppid: fields.get(1)?.parse().ok()?,
+        cpu_ticks: field(11)? + field(12)?,
+        start_ticks: field(19)?,
+        rss_pages: field(21)?,
+    })
+}
+
+/// Get all processes with `ps`, on systems without `/proc`
+#[cfg(unix)]
+fn ps_snapshot() -> Result<Vec<Info>> {
+    let mut cmd = std::process::Command::new("ps");
+    cmd.arg("-A");
+    // A column per option, as procps would take the rest of a list as the header
+    for column in [
+        "pid=", "ppid=", "ruid=", "state=", "pcpu=", "rss=", "etime=", "args=",
+    ] {
+        cmd.arg("-o").arg(column);
+    }
+    let output = command_output(&mut cmd, "ps")?;
+
+    let mut processes: Vec<Info> = output
+        .lines()
+        .filter_map(|line| {
+            let mut rest = line.trim_start();
+            let mut columns = Vec::new();
+            for _ in 0..7 {
+                let end = rest.find(char::is_whitespace)?;
+                columns.push(&rest[..end]);
+                rest = rest[end..].trim_start();
+            }
+            let command = rest.to_string();
+            let name = command.split_whitespace().next()?;
+            Some(Info {
+                pid: columns[0].parse().ok()?,
+                ppid: columns[1].parse().ok()?,
+                uid: columns[2].parse().ok()?,
+                state: columns[3].chars().next()?,
+                name: name.rsplit('/').next().unwrap_or(name).to_string(),
+                cpu_tenths: parse_decimal(columns[4], 10)?,
+                rss: columns[5].parse::<u64>().ok()? * 1024,
+                elapsed: parse_elapsed(columns[6])?,
+                command,
+            })
+        })
+        .collect();
+    processes.sort_by_key(|info| info.pid);
+    Ok(processes)
+}
+
+/// Get a process and all the processes it started, directly or not
+fn descendants(processes: &[Info], pid: u32) -> HashSet<u32> {
+    let mut found = HashSet::from([pid]);
+    let mut stack = vec![pid];
+    while let Some(parent) = stack.pop() {
+        for info in processes {
+            if info.ppid == parent && info.pid != parent && found.insert(info.pid) {
+                stack.push(info.pid);
+            }
+        }
+    }
+    found
+}
+
+/// Order processes by parent, returning their indices and depths
+///
+/// Processes whose parent is not listed are at the top, and children follow their
+/// parent in the order of the list. Processes whose parents form a cycle are shown at
+/// the top from the first one of the list.
+fn tree_order(processes: &[Info]) -> Vec<(usize, usize)> {
+    let pids: HashSet<u32> = processes.iter().map(|info| info.pid).collect();
+    let mut children: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    let mut roots = Vec::new();
+    for (index, info) in processes.iter().enumerate() {
+        if info.ppid != info.pid && pids.contains(&info.ppid) {
+            children.entry(info.ppid).or_default().push(index);
+        } else {
+            roots.push(index);
+        }
+    }
+
+    let mut order = Vec::with_capacity(processes.len());
+    let mut visited = vec![false; processes.len()];
+    // Processes in a cycle have no root, so the first unvisited one starts a tree
+    for root in roots.into_iter().chain(0..processes.len()) {
+        let mut stack = vec![(root, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            if std::mem::replace(&mut visited[index], true) {
+                continue;
+            }
+            order.push((index, depth));
+            if let Some(children) = children.get(&processes[index].pid) {
+                stack.extend(children.iter().rev().map(|&child| (child, depth + 1)));
+            }
+        }
+    }
+    order
+}
+
+/// Get the name of a user, or the user ID when it has no name
+#[cfg(unix)]
+fn user_name(uid: u32) -> String {
+    use std::ffi::CStr;
+
+    let mut buffer = vec![0; 1024];
+    loop {
+        // SAFETY: passwd is plain data filled in by getpwuid_r, which only writes to
+        // the buffer within the given length
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let code = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &raw mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &raw mut result,
+            )
+        };
+        if code == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() || passwd.pw_name.is_null() {
+            return uid.to_string();
+        }
+        // SAFETY: pw_name points to a NUL-terminated string in the buffer
+        let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+        return name.to_string_lossy().into_owned();
+    }
+}
+
+/// Get the name of a user, or the user ID when it has no name
+#[cfg(not(unix))]
+fn user_name(uid: u32) -> String {
+    uid.to_string()
+}
+
+/// Parse a non-negative decimal number such as `12.34` into units of `1 / scale`
+#[cfg(unix)]
+fn parse_decimal(text: &str, scale: u64) -> Option<u64> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let mut value = whole.parse::<u64>().ok()? * scale;
+    let mut unit = scale;
+    for digit in fraction.chars() {
+        unit /= 10;
+        if unit == 0 {
+            break;
+        }
+        value += u64::from(digit.to_digit(10)?) * unit;
+    }
+    Some(value)
+}
+
+/// Parse an elapsed time formatted as `[[days-]hours:]minutes:seconds`
+#[cfg(unix)]
+fn parse_elapsed(text: &str) -> Option<u64> {
+    let (days, time) = match text.split_once('-') {
+        Some((days, time)) => (Some(days.parse::<u64>().ok()?), time),
+        None => (None, text),
+    };
+    let parts: Vec<&str> = time.split(':').collect();
+    // Days are only given with hours
+    let expected = if days.is_some() { 3..=3 } else { 2..=3 };
+    if !expected.contains(&parts.len()) {
+        return None;
+    }
+    let seconds = parts.iter().try_fold(0, |total, part| {
+        Some(total * 60 + part.parse::<u64>().ok()?)
+    })?;
+    Some(days.unwrap_or(0) * 86_400 + seconds)
+}
+
+/// Format an elapsed time like `ps`: `[[days-]hours:]minutes:seconds`
+fn format_elapsed(seconds: u64) -> String {
+    let (days, hours) = (seconds / 86_400, seconds / 3600 % 24);
+    let (minutes, seconds) = (seconds / 60 % 60, seconds % 60);
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Format a number of tenths with one decimal, such as `12.3`
+fn format_tenths(tenths: u64) -> String {
+    format!("{}.{}", tenths / 10, tenths % 10)
+}
+
+/// Format a size in bytes with a binary unit, such as `12.3M`
+fn format_size(bytes: u64) -> String {
+    let (unit, suffix) = [(1 << 30, "G"), (1 << 20, "M")]
+        .into_iter()
+        .find(|&(unit, _)| bytes >= unit)
+        .unwrap_or((1 << 10, "K"));
+    format!("{}{}", format_tenths(bytes * 10 / unit), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(pid: u32, parent: u32) -> Info {
+        Info {
+            pid,
+            ppid: parent,
+            uid: 1000,
+            state: 'S',
+            name: format!("p{}", pid),
+            command: format!("/bin/p{}", pid),
+            cpu_tenths: 0,
+            rss: 0,
+            elapsed: 0,
+        }
+    }
+
+    #[test]
+    fn descendants_include_the_whole_subtree() {
+        let processes = [
+            info(1, 0),
+            info(2, 1),
+            info(3, 2),
+            info(4, 1),
+            info(5, 3),
+            info(6, 7),
+        ];
+        assert_eq!(descendants(&processes, 2), HashSet::from([2, 3, 5]));
+        assert_eq!(descendants(&processes, 6), HashSet::from([6]));
+        assert_eq!(descendants(&processes, 9), HashSet::from([9]));
+    }
+
+    #[test]
+    fn descendants_stop_at_cycles() {
+        let processes = [info(1, 2), info(2, 1), info(3, 3)];
+        assert_eq!(descendants(&processes, 1), HashSet::from([1, 2]));
+        assert_eq!(descendants(&processes, 3), HashSet::from([3]));
+    }
+
+    #[test]
+    fn tree_order_puts_children_below_their_parent() {
+        let processes = [info(1, 0), info(2, 1), info(3, 1), info(4, 2), info(5, 0)];
+        assert_eq!(
+            tree_order(&processes),
+            [(0, 0), (1, 1), (3, 2), (2, 1), (4, 0)]
+        );
+    }
+
+    #[test]
+    fn tree_order_keeps_orphans_and_cycles() {
+        // 2 and 3 are each other's parent, 4's parent is not listed, 5 is its own parent
+        let processes = [info(2, 3), info(3, 2), info(4, 9), info(5, 5)];
+        assert_eq!(tree_order(&processes), [(2, 0), (3, 0), (0, 0), (1, 1)]);
+
+        let processes = [info(1, 0), info(4, 9), info(5, 5), info(6, 4)];
+        assert_eq!(tree_order(&processes), [(0, 0), (1, 0), (3, 1), (2, 0)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_out_excluded_process_trees() -> Result<()> {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn()?;
+        let pid = child.id().to_string();
+        let list = |source: Process| -> Result<Vec<String>> {
+            let mut candidates = Vec::new();
+            source.stream(&mut candidates)?;
+            Ok(candidates
+                .into_iter()
+                .map(|candidate| candidate.value)
+                .collect())
+        };
+        // The child of the test is left out like the selector of anyframe-rs, and the
+        // parent of the test like the process waiting for a popup
+        let listed = list(Process::new());
+        let parent = std::os::unix::process::parent_id();
+        let excluded = list(Process::new().excluding(parent));
+        child.kill()?;
+        child.wait()?;
+
+        let (listed, excluded) = (listed?, excluded?);
+        assert!(!listed.contains(&pid));
+        assert!(listed.contains(&parent.to_string()));
+        assert!(!excluded.contains(&parent.to_string()));
+        assert!(!excluded.contains(&std::process::id().to_string()));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parses_decimals() {
+        assert_eq!(parse_decimal("12.34", 100), Some(1234));
+        assert_eq!(parse_decimal("12", 10), Some(120));
+        assert_eq!(parse_decimal("12.", 10), Some(120));
+        assert_eq!(parse_decimal("0.5", 10), Some(5));
+        // Digits below the unit are truncated, not rounded
+        assert_eq!(parse_decimal("1.99", 10), Some(19));
+        assert_eq!(parse_decimal("0.09", 10), Some(0));
+        assert_eq!(parse_decimal("3.999", 1), Some(3));
+        assert_eq!(parse_decimal("", 10), None);
+        assert_eq!(parse_decimal(".5", 10), None);
+        assert_eq!(parse_decimal("1.x", 10), None);
+        assert_eq!(parse_decimal("-1", 10), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parses_elapsed_times() {
+        assert_eq!(parse_elapsed("00:07"), Some(7));
+        assert_eq!(parse_elapsed("12:34"), Some(754));
+        assert_eq!(parse_elapsed("01:02:03"), Some(3723));
+        assert_eq!(parse_elapsed("2-01:02:03"), Some(2 * 86_400 + 3723));
+        assert_eq!(parse_elapsed(""), None);
+        assert_eq!(parse_elapsed("7"), None);
+        assert_eq!(parse_elapsed("1:2:3:4"), None);
+        assert_eq!(parse_elapsed("2-03:04"), None);
+        assert_eq!(parse_elapsed("x-01:02:03"), None);
+        assert_eq!(parse_elapsed("01:xx"), None);
+        assert_eq!(parse_elapsed("01::02"), None);
+    }
+
+    #[test]
+    fn formats_elapsed_times() {
+        assert_eq!(format_elapsed(0), "00:00");
+        assert_eq!(format_elapsed(59), "00:59");
+        assert_eq!(format_elapsed(3599), "59:59");
+        assert_eq!(format_elapsed(3600), "01:00:00");
+        assert_eq!(format_elapsed(86_399), "23:59:59");
+        assert_eq!(format_elapsed(86_400), "1-00:00:00");
+        assert_eq!(format_elapsed(12 * 86_400 + 3723), "12-01:02:03");
+    }
+
+    #[test]
+    fn formats_tenths() {
+        assert_eq!(format_tenths(0), "0.0");
+        assert_eq!(format_tenths(9), "0.9");
+        assert_eq!(format_tenths(1234), "123.4");
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0.0K");
+        assert_eq!(format_size(1023), "0.9K");
+        assert_eq!(format_size(1024), "1.0K");
+        assert_eq!(format_size((1 << 20) - 1), "1023.9K");
+        assert_eq!(format_size(1 << 20), "1.0M");
+        assert_eq!(format_size(1536 << 10), "1.5M");
+        assert_eq!(format_size((1 << 30) - 1), "1023.9M");
+        assert_eq!(format_size(1 << 30), "1.0G");
+        assert_eq!(format_size(5 << 40), "5120.0G");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parses_stat_names_with_parentheses_and_spaces() {
+        let stat = "1234 (my (odd) name) S 1 1234 1234 0 -1 4194560 100 0 0 0 25 15 0 0 \
+                    20 0 1 0 5000 10000000 300 18446744073709551615\n";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                name: "my (odd) name",
+                state: 'S',
+                ppid: 1,
+                cpu_ticks: 40,
+                start_ticks: 5000,
+                rss_pages: 300,
+            })
+        );
+
+        let stat = "7 ()) R 2 0 0 0 -1 0 0 0 0 0 1 2 0 0 20 0 1 0 9 0 0\n";
+        assert_eq!(parse_stat(stat).map(|stat| stat.name), Some(")"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn rejects_truncated_stats() {
+        assert_eq!(parse_stat(""), None);
+        assert_eq!(parse_stat("1 (name S 1"), None);
+        assert_eq!(parse_stat("1 (name) S 1 1 1 0 -1 0"), None);
+    }
+}
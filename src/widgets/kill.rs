@@ -0,0 +1,469 @@
+//! Kill widget
+//!
+//! Sends a signal to the selected processes with `kill(2)`, after confirming the
+//! selection and the signal through the same selector.
+
+use super::{Picker, Selected, Widget};
+use crate::{
+    actions::ZleOutput,
+    error,
+    selectors::{SelectOptions, Selector},
+    sources::{Candidate, Source},
+    Result,
+};
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval between two checks of whether the signalled processes have exited
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Signal sent by the [`Kill`] widget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGTERM`: ask the process to terminate
+    Term,
+    /// `SIGKILL`: kill the process immediately, which it cannot handle
+    Kill,
+    /// `SIGHUP`: hang up, which makes many daemons reload their configuration
+    Hup,
+    /// `SIGINT`: interrupt, as with Ctrl-C
+    Int,
+    /// `SIGSTOP`: suspend the process, which it cannot handle
+    Stop,
+    /// `SIGCONT`: resume a suspended process
+    Cont,
+}
+
+impl Signal {
+    /// Every signal, in the order offered by the signal picker
+    pub const ALL: [Self; 6] = [
+        Self::Term,
+        Self::Kill,
+        Self::Hup,
+        Self::Int,
+        Self::Stop,
+        Self::Cont,
+    ];
+
+    /// Name of the signal without the `SIG` prefix, such as `TERM`
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Term => "TERM",
+            Self::Kill => "KILL",
+            Self::Hup => "HUP",
+            Self::Int => "INT",
+            Self::Stop => "STOP",
+            Self::Cont => "CONT",
+        }
+    }
+
+    /// Short description shown by the signal picker
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Term => "terminate",
+            Self::Kill => "kill immediately",
+            Self::Hup => "hang up",
+            Self::Int => "interrupt",
+            Self::Stop => "suspend",
+            Self::Cont => "resume",
+        }
+    }
+
+    /// Number of the signal
+    #[cfg(unix)]
+    const fn number(self) -> libc::c_int {
+        match self {
+            Self::Term => libc::SIGTERM,
+            Self::Kill => libc::SIGKILL,
+            Self::Hup => libc::SIGHUP,
+            Self::Int => libc::SIGINT,
+            Self::Stop => libc::SIGSTOP,
+            Self::Cont => libc::SIGCONT,
+        }
+    }
+
+    /// Whether the signal asks the process to exit, and can thus be escalated to
+    /// `SIGKILL`
+    const fn terminates(self) -> bool {
+        matches!(self, Self::Term | Self::Hup | Self::Int)
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SIG{}", self.name())
+    }
+}
+
+impl FromStr for Signal {
+    type Err = error::AnyframeError;
+
+    /// Parse a signal name, case-insensitively and with or without the `SIG` prefix
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        Self::ALL
+            .into_iter()
+            .find(|signal| signal.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|signal| signal.name()).collect();
+                error::AnyframeError::WidgetError(format!(
+                    "Unknown signal '{}' (available: {})",
+                    s,
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+/// Selected process
+struct Target {
+    pid: u32,
+    /// Name of the process, for the report
+    name: String,
+    /// Line of the process in the selector, for the confirmation
+    display: String,
+}
+
+/// Kill process widget
+///
+/// The selected processes are listed in a confirmation, through the same selector,
+/// where the signal is picked: `SIGTERM` first, or only the signal set with
+/// [`signal`](Kill::signal). Cancelling the confirmation sends nothing.
+///
+/// The signals are sent natively rather than by running `kill`, and the outcome for
+/// each process, such as a permission error, is shown below the command line. With
+/// [`escalate`](Kill::escalate), the processes that are still running some time after
+/// a terminating signal are sent `SIGKILL`. Signals can only be sent on Unix; elsewhere
+/// the widget fails without running the selector.
+pub struct Kill<S: Source, F: Selector> {
+    picker: Picker<S, F>,
+    signal: Option<Signal>,
+    confirm: bool,
+    escalate: Option<Duration>,
+}
+
+impl<S: Source, F: Selector> Kill<S, F> {
+    /// Create a new Kill widget, confirming the selection with the signal picker
+    pub const fn new(picker: Picker<S, F>) -> Self {
+        Self {
+            picker,
+            signal: None,
+            confirm: true,
+            escalate: None,
+        }
+    }
+
+    /// Send this signal instead of picking one
+    #[must_use]
+    pub fn signal(mut self, signal: Signal) -> Self {
+        self.signal = Some(signal);
+        self
+    }
+
+    /// Set whether to confirm the selection before signalling (enabled by default)
+    ///
+    /// Without the confirmation, the signal set with [`signal`](Self::signal) is sent
+    /// right away, `SIGTERM` by default.
+    #[must_use]
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.confirm = confirm;
+        self
+    }
+
+    /// Send `SIGKILL` to the processes still running this long after a terminating
+    /// signal (`SIGTERM`, `SIGHUP` or `SIGINT`)
+    #[must_use]
+    pub fn escalate(mut self, timeout: Duration) -> Self {
+        self.escalate = Some(timeout);
+        self
+    }
+
+    /// Ask which signal to send to the targets, listed above the signals
+    fn pick_signal(&self, targets: &[Target]) -> Result<Signal> {
+        let mut candidates = vec![Candidate::header_line(format!(
+            "Signal {} process(es):",
+            targets.len()
+        ))];
+        candidates.extend(
+            targets
+                .iter()
+                .map(|target| Candidate::header_line(format!("  {}", target.display))),
+        );
+        let signals = self
+            .signal
+            .as_ref()
+            .map_or(&Signal::ALL[..], std::slice::from_ref);
+        candidates.extend(signals.iter().map(|signal| {
+            Candidate::with_display(
+                format!("{:<4}  {}", signal.name(), signal.description()),
+                signal.name(),
+            )
+        }));
+
+        let options = SelectOptions {
+            query: None,
+            multi: false,
+            prompt: Some("signal> ".to_string()),
+            header: None,
+            sort: Some(false),
+            expect: Vec::new(),
+            ..self.picker.options.clone()
+        };
+        let selection = self.picker.selector.select(&candidates, &options)?;
+        selection
+            .items
+            .iter()
+            .find(|candidate| !candidate.header)
+            .ok_or(error::AnyframeError::NoMatch)?
+            .value
+            .parse()
+    }
+}
+
+impl<S: Source, F: Selector> Widget for Kill<S, F> {
+    fn run(&self) -> Result<ZleOutput> {
+        if !cfg!(unix) {
+            return Err(error::AnyframeError::WidgetError(
+                "Sending signals is not supported on this platform".to_string(),
+            ));
+        }
+
+        let selected = match self.picker.select(true, false)? {
+            Selected::Items(items) => items,
+            Selected::Performed(output) => return Ok(output),
+        };
+
+        let targets = selected
+            .into_iter()
+            .map(|candidate| {
+                let pid = candidate
+                    .value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|pid| *pid > 0)
+                    .ok_or_else(|| {
+                        error::AnyframeError::WidgetError(format!(
+                            "Not a process ID: '{}'",
+                            candidate.value
+                        ))
+                    })?;
+                let name = candidate.metadata.get("name").cloned().unwrap_or_default();
+                Ok(Target {
+                    pid,
+                    name,
+                    display: candidate.display,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let signal = if self.confirm {
+            self.pick_signal(&targets)?
+        } else {
+            self.signal.unwrap_or(Signal::Term)
+        };
+
+        let results: Vec<_> = targets
+            .iter()
+            .map(|target| send(target.pid, signal))
+            .collect();
+        let mut outcomes: Vec<String> = results
+            .iter()
+            .map(|result| outcome(signal, result))
+            .collect();
+
+        if let Some(timeout) = self.escalate.filter(|_| signal.terminates()) {
+            // Only the processes that received the first signal are waited for
+            let mut pending: Vec<usize> =
+                (0..targets.len()).filter(|&i| results[i].is_ok()).collect();
+            let deadline = Instant::now() + timeout;
+            loop {
+                pending.retain(|&i| is_running(targets[i].pid));
+                if pending.is_empty() || Instant::now() >= deadline {
+                    break;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            for i in pending {
+                let escalated = send(targets[i].pid, Signal::Kill);
+                outcomes[i].push_str(&escalation(&escalated, timeout));
+            }
+        }
+
+        Ok(ZleOutput::new().message(report(&targets, &outcomes)))
+    }
+
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+}
+
+/// Describe the outcome of sending a signal to a process
+fn outcome(signal: Signal, result: &std::result::Result<(), String>) -> String {
+    match result {
+        Ok(()) => format!("{} sent", signal),
+        Err(reason) => format!("{} failed: {}", signal, reason),
+    }
+}
+
+/// Describe the outcome of sending `SIGKILL` to a process still running after the
+/// timeout, to be appended to the outcome of the first signal
+fn escalation(result: &std::result::Result<(), String>, timeout: Duration) -> String {
+    match result {
+        Ok(()) => format!(", {} sent after {}s", Signal::Kill, timeout.as_secs()),
+        Err(reason) => format!(", {} failed: {}", Signal::Kill, reason),
+    }
+}
+
+/// Describe the outcome for each process, one line per process
+fn report(targets: &[Target], outcomes: &[String]) -> String {
+    let lines: Vec<String> = targets
+        .iter()
+        .zip(outcomes)
+        .map(|(target, outcome)| {
+            if target.name.is_empty() {
+                format!("{}: {}", target.pid, outcome)
+            } else {
+                format!("{} ({}): {}", target.pid, target.name, outcome)
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Send a signal to a process, describing why it could not be sent
+#[cfg(unix)]
+fn send(pid: u32, signal: Signal) -> std::result::Result<(), String> {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return Err("no such process".to_string());
+    };
+    // SAFETY: kill has no memory-safety preconditions; the pid is positive, so only
+    // this process is signalled
+    if unsafe { libc::kill(pid, signal.number()) } == 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    Err(match err.raw_os_error() {
+        Some(libc::EPERM) => "permission denied".to_string(),
+        Some(libc::ESRCH) => "no such process".to_string(),
+        _ => err.to_string(),
+    })
+}
+
+/// Send a signal to a process, which is not supported outside Unix
+#[cfg(not(unix))]
+fn send(_pid: u32, _signal: Signal) -> std::result::Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
+
+/// Check whether a process is still running
+///
+/// Zombies, which have exited but not been reaped by their parent yet, are not running.
+/// A process that exists but cannot be signalled, such as one owned by another user, is
+/// running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // The state follows the name, which is in parentheses and may contain spaces
+        return stat
+            .rsplit_once(')')
+            .is_some_and(|(_, rest)| !rest.trim_start().starts_with(['Z', 'X']));
+    }
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks whether the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // The process exists but may not be signalled by this user
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Check whether a process is still running, which is never known outside Unix
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(pid: u32, name: &str) -> Target {
+        Target {
+            pid,
+            name: name.to_string(),
+            display: String::new(),
+        }
+    }
+
+    #[test]
+    fn parses_signal_names() -> Result<()> {
+        assert_eq!("TERM".parse::<Signal>()?, Signal::Term);
+        assert_eq!("kill".parse::<Signal>()?, Signal::Kill);
+        assert_eq!("SIGHUP".parse::<Signal>()?, Signal::Hup);
+        assert_eq!("sigint".parse::<Signal>()?, Signal::Int);
+        assert_eq!("SigCont".parse::<Signal>()?, Signal::Cont);
+        for signal in Signal::ALL {
+            assert_eq!(signal.to_string().parse::<Signal>()?, signal);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_signals() {
+        for name in ["USR1", "SIG", "", "9", "SIGSIGTERM"] {
+            assert_eq!(
+                name.parse::<Signal>().err().map(|e| e.to_string()),
+                Some(format!(
+                    "Widget error: Unknown signal '{}' (available: TERM, KILL, HUP, INT, STOP, CONT)",
+                    name
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn describes_outcomes() {
+        assert_eq!(outcome(Signal::Term, &Ok(())), "SIGTERM sent");
+        assert_eq!(
+            outcome(Signal::Stop, &Err("permission denied".to_string())),
+            "SIGSTOP failed: permission denied"
+        );
+        assert_eq!(
+            escalation(&Ok(()), Duration::from_secs(5)),
+            ", SIGKILL sent after 5s"
+        );
+        assert_eq!(
+            escalation(&Err("no such process".to_string()), Duration::from_secs(5)),
+            ", SIGKILL failed: no such process"
+        );
+    }
+
+    #[test]
+    fn reports_each_process_on_a_line() {
+        let targets = [target(42, "sleep"), target(7, "")];
+        let outcomes = [
+            "SIGTERM sent, SIGKILL sent after 3s".to_string(),
+            "SIGTERM failed: permission denied".to_string(),
+        ];
+        assert_eq!(
+            report(&targets, &outcomes),
+            "42 (sleep): SIGTERM sent, SIGKILL sent after 3s\n\
+             7: SIGTERM failed: permission denied"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn processes_of_other_users_are_running() {
+        // PID 1 belongs to root, which other users cannot signal
+        assert!(is_running(1));
+        assert!(is_running(std::process::id()));
+    }
+}
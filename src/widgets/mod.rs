@@ -2,290 +2,403 @@
 //!
 //! Widgets combine sources, selectors, and actions to create useful functionalities.
 
-use crate::{actions::Action, selectors::Selector, sources::Source, Result};
+use crate::{
+    actions::{shell_quote, Action, ChangeDirectory, Execute, Insert, Put, ZleOutput},
+    error,
+    selectors::{SelectOptions, Selector},
+    sources::{Candidate, Source},
+    Result,
+};
+
+pub mod kill;
+
+pub use kill::{Kill, Signal};
 
 /// Trait for widgets
 pub trait Widget {
-    /// Run the widget
-    fn run(&self) -> Result<()>;
+    /// Run the widget and return the changes to apply to the calling shell
+    fn run(&self) -> Result<ZleOutput>;
 
     /// Get the name of the widget
     fn name(&self) -> &str;
 }
 
-/// Execute history widget
-pub struct ExecuteHistory<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
+/// Collect the values of the selected candidates, shell-quoted if requested
+fn values(selected: &[Candidate], quote: bool) -> Vec<String> {
+    selected
+        .iter()
+        .map(|c| {
+            if quote {
+                shell_quote(&c.value)
+            } else {
+                c.value.clone()
+            }
+        })
+        .collect()
 }
 
-impl<S: Source, F: Selector, A: Action> ExecuteHistory<S, F, A> {
-    /// Create a new ExecuteHistory widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
-        Self {
-            source,
-            selector,
-            action,
+/// Pass the selected values to an action, substituting them into a template if given
+///
+/// Values substituted into a template are always quoted.
+fn perform(
+    action: &dyn Action,
+    template: Option<&str>,
+    quote: bool,
+    selected: &[Candidate],
+) -> Result<ZleOutput> {
+    match template {
+        Some(template) => {
+            action.perform(&[template.replace("{}", &values(selected, true).join(" "))])
         }
+        None => action.perform(&values(selected, quote)),
     }
 }
 
-impl<S: Source, F: Selector, A: Action> Widget for ExecuteHistory<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
-        self.action.perform(&selected)?;
-
-        Ok(())
-    }
-
-    fn name(&self) -> &'static str {
-        "execute-history"
-    }
+/// Alternate action of a widget, run when the selection is accepted with its key
+///
+/// With a template, the selected values are shell-quoted, joined with spaces and
+/// substituted for every `{}` of the template, e.g. `git branch -d {}`.
+pub struct KeyAction {
+    action: Box<dyn Action>,
+    template: Option<String>,
 }
 
-/// Cd ghq repository widget
-pub struct CdGhqRepository<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
-}
-
-impl<S: Source, F: Selector, A: Action> CdGhqRepository<S, F, A> {
-    /// Create a new CdGhqRepository widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
+impl KeyAction {
+    /// Create an alternate action receiving the selected values
+    #[must_use]
+    pub fn new(action: Box<dyn Action>) -> Self {
         Self {
-            source,
-            selector,
             action,
+            template: None,
         }
     }
-}
-
-impl<S: Source, F: Selector, A: Action> Widget for CdGhqRepository<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
-        self.action.perform(&selected)?;
 
-        Ok(())
+    /// Substitute the selected values into a template before passing them to the action
+    #[must_use]
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
     }
 
-    fn name(&self) -> &'static str {
-        "cd-ghq-repository"
+    /// Perform the action on the selected candidates, shell-quoting their values if
+    /// requested by the widget
+    pub fn perform(&self, selected: &[Candidate], quote: bool) -> Result<ZleOutput> {
+        perform(&self.action, self.template.as_deref(), quote, selected)
     }
 }
 
-/// Checkout git branch widget
-pub struct CheckoutGitBranch<S: Source, F: Selector, A: Action> {
+/// Outcome of the selection of a widget
+enum Selected {
+    /// Candidates accepted with Enter, for the widget's own action
+    Items(Vec<Candidate>),
+    /// Output of the alternate action bound to the key that accepted the selection
+    Performed(ZleOutput),
+}
+
+/// Source and selector of a widget, with the selector options and alternate actions
+///
+/// Every widget selects its candidates through a picker, so the options and the keys
+/// are configured the same way whatever the widget does with the selection.
+pub struct Picker<S: Source, F: Selector> {
     source: S,
     selector: F,
-    action: A,
+    options: SelectOptions,
+    keys: Vec<(String, KeyAction)>,
 }
 
-impl<S: Source, F: Selector, A: Action> CheckoutGitBranch<S, F, A> {
-    /// Create a new CheckoutGitBranch widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
+impl<S: Source, F: Selector> Picker<S, F> {
+    /// Create a new picker with the default options and no alternate actions
+    pub fn new(source: S, selector: F) -> Self {
         Self {
             source,
             selector,
-            action,
+            options: SelectOptions::default(),
+            keys: Vec::new(),
         }
     }
-}
 
-impl<S: Source, F: Selector, A: Action> Widget for CheckoutGitBranch<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
+    /// Set the options passed to the selector, such as the prompt and the header
+    ///
+    /// Multi-select is set by the widget instead.
+    #[must_use]
+    pub fn options(mut self, options: SelectOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-        // Extract the first field (branch name) from the selected line
-        let branch_name = selected.split_whitespace().next().unwrap_or("");
+    /// Bind alternate actions to keys accepting the selection
+    #[must_use]
+    pub fn keys(mut self, keys: impl IntoIterator<Item = (String, KeyAction)>) -> Self {
+        self.keys.extend(keys);
+        self
+    }
 
-        // Execute git checkout command
-        self.action
-            .perform(&format!("git checkout {}", branch_name))?;
+    /// Run the selector, expecting the keys of the alternate actions
+    ///
+    /// Header lines are never acted on, even if a selector returns them. With `quote`,
+    /// the alternate actions receive the values shell-quoted.
+    fn select(&self, multi: bool, quote: bool) -> Result<Selected> {
+        let mut expect = self.options.expect.clone();
+        for (key, _) in &self.keys {
+            if !expect.contains(key) {
+                expect.push(key.clone());
+            }
+        }
 
-        Ok(())
-    }
+        let options = self.options.clone().multi(multi).expect(expect);
+        let mut selection = self.selector.select(&self.source, &options)?;
+        selection.items.retain(|candidate| !candidate.header);
+        if selection.items.is_empty() {
+            return Err(error::AnyframeError::NoMatch);
+        }
 
-    fn name(&self) -> &'static str {
-        "checkout-git-branch"
+        let key_action = selection
+            .key
+            .as_ref()
+            .and_then(|key| self.keys.iter().find(|(k, _)| k == key));
+        match key_action {
+            Some((_, action)) => Ok(Selected::Performed(
+                action.perform(&selection.items, quote)?,
+            )),
+            None => Ok(Selected::Items(selection.items)),
+        }
     }
 }
 
-/// Insert git branch widget
-pub struct InsertGitBranch<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
+/// Generic widget composing any source, selector and action
+///
+/// The values of the selected candidates are passed to the action. With a template,
+/// they are shell-quoted, joined with spaces and substituted for every `{}` of the
+/// template, and the action receives the resulting single item, e.g. `docker logs {}`.
+pub struct Pipeline<S: Source, F: Selector, A: Action> {
+    name: String,
+    picker: Picker<S, F>,
     action: A,
+    multi: bool,
+    quote: bool,
+    template: Option<String>,
 }
 
-impl<S: Source, F: Selector, A: Action> InsertGitBranch<S, F, A> {
-    /// Create a new InsertGitBranch widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
+impl<S: Source, F: Selector, A: Action> Pipeline<S, F, A> {
+    /// Create a new Pipeline widget selecting a single candidate
+    pub fn new(name: impl Into<String>, picker: Picker<S, F>, action: A) -> Self {
         Self {
-            source,
-            selector,
+            name: name.into(),
+            picker,
             action,
+            multi: false,
+            quote: false,
+            template: None,
         }
     }
-}
-
-impl<S: Source, F: Selector, A: Action> Widget for InsertGitBranch<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
 
-        // Extract the first field (branch name) from the selected line
-        let branch_name = selected.split_whitespace().next().unwrap_or("");
-
-        // Insert branch name
-        self.action.perform(branch_name)?;
+    /// Allow selecting several candidates
+    #[must_use]
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
 
-        Ok(())
+    /// Shell-quote the selected values passed to the action and to the alternate
+    /// actions, such as paths inserted into the command line
+    #[must_use]
+    pub fn quote(mut self, quote: bool) -> Self {
+        self.quote = quote;
+        self
     }
 
-    fn name(&self) -> &'static str {
-        "insert-git-branch"
+    /// Substitute the selected values into a template before passing them to the action
+    #[must_use]
+    pub fn template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
     }
 }
 
-/// Git add widget
-pub struct GitAdd<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
-}
+impl<S: Source, F: Selector, A: Action> Widget for Pipeline<S, F, A> {
+    fn run(&self) -> Result<ZleOutput> {
+        let selected = match self.picker.select(self.multi, self.quote)? {
+            Selected::Items(items) => items,
+            Selected::Performed(output) => return Ok(output),
+        };
+
+        perform(
+            &self.action,
+            self.template.as_deref(),
+            self.quote,
+            &selected,
+        )
+    }
 
-impl<S: Source, F: Selector, A: Action> GitAdd<S, F, A> {
-    /// Create a new GitAdd widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
-        Self {
-            source,
-            selector,
-            action,
-        }
+    fn name(&self) -> &str {
+        &self.name
     }
 }
 
-impl<S: Source, F: Selector, A: Action> Widget for GitAdd<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
-
-        // Extract the second field (file path) from the selected line
-        let file_path = selected.split_whitespace().nth(1).unwrap_or("");
-
-        // Execute git add command
-        self.action.perform(&format!("git add --{}", file_path))?;
+/// Execute history widget: runs the selected command
+pub fn execute_history<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, Execute> {
+    Pipeline::new("execute-history", picker, Execute)
+}
 
-        Ok(())
-    }
+/// Put history widget: puts the selected command into the buffer
+pub fn put_history<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, Put> {
+    Pipeline::new("put-history", picker, Put::new(false))
+}
 
-    fn name(&self) -> &'static str {
-        "git-add"
-    }
+/// Cdr widget: changes to the selected recent directory
+pub fn cdr<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, ChangeDirectory> {
+    Pipeline::new("cdr", picker, ChangeDirectory)
 }
 
-/// Put history widget
-pub struct PutHistory<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
+/// Cd directory widget: changes to the selected directory
+pub fn cd_directory<S: Source, F: Selector>(
+    picker: Picker<S, F>,
+) -> Pipeline<S, F, ChangeDirectory> {
+    Pipeline::new("cd-directory", picker, ChangeDirectory)
 }
 
-impl<S: Source, F: Selector, A: Action> PutHistory<S, F, A> {
-    /// Create a new PutHistory widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
-        Self {
-            source,
-            selector,
-            action,
-        }
-    }
+/// Cd ghq repository widget: changes to the selected repository
+pub fn cd_ghq_repository<S: Source, F: Selector>(
+    picker: Picker<S, F>,
+) -> Pipeline<S, F, ChangeDirectory> {
+    Pipeline::new("cd-ghq-repository", picker, ChangeDirectory)
 }
 
-impl<S: Source, F: Selector, A: Action> Widget for PutHistory<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
-        self.action.perform(&selected)?;
+/// Checkout git branch widget: runs `git checkout` with the selected branch
+pub fn checkout_git_branch<S: Source, F: Selector>(
+    picker: Picker<S, F>,
+) -> Pipeline<S, F, Execute> {
+    Pipeline::new("checkout-git-branch", picker, Execute).template("git checkout {}")
+}
 
-        Ok(())
-    }
+/// Insert git branch widget
+///
+/// Several selected branch names are inserted separated by spaces.
+pub fn insert_git_branch<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, Insert> {
+    Pipeline::new("insert-git-branch", picker, Insert).multi(true)
+}
 
-    fn name(&self) -> &'static str {
-        "put-history"
-    }
+/// Git add widget
+///
+/// All selected files are staged with a single `git add`.
+pub fn git_add<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, Execute> {
+    Pipeline::new("git-add", picker, Execute)
+        .multi(true)
+        .template("git add -- {}")
 }
 
-/// Cdr widget
-pub struct Cdr<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
+/// Insert filename widget
+///
+/// Selected paths are shell-quoted and inserted separated by spaces, including for the
+/// alternate actions.
+pub fn insert_filename<S: Source, F: Selector>(picker: Picker<S, F>) -> Pipeline<S, F, Insert> {
+    Pipeline::new("insert-filename", picker, Insert)
+        .multi(true)
+        .quote(true)
 }
 
-impl<S: Source, F: Selector, A: Action> Cdr<S, F, A> {
-    /// Create a new Cdr widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
-        Self {
-            source,
-            selector,
-            action,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selectors::{Filter, Selection};
+
+    /// Selector accepting every candidate with a key
+    struct AcceptWith(&'static str);
+
+    impl Selector for AcceptWith {
+        fn select(&self, source: &dyn Source, options: &SelectOptions) -> Result<Selection> {
+            assert!(options.expect.iter().any(|key| key == self.0));
+            Ok(Selection {
+                key: Some(self.0.to_string()),
+                items: source.candidates()?,
+            })
+        }
+
+        fn name(&self) -> &'static str {
+            "accept-with"
         }
     }
-}
 
-impl<S: Source, F: Selector, A: Action> Widget for Cdr<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
-        self.action.perform(&selected)?;
+    /// Picker selecting the best match of the query, as with `--select-first`
+    fn select_first(candidates: &[&str], query: &str) -> Picker<Vec<Candidate>, Filter> {
+        let source = candidates
+            .iter()
+            .map(|&value| Candidate::new(value))
+            .collect();
+        Picker::new(source, Filter::new().first(true)).options(SelectOptions::new().query(query))
+    }
 
+    #[test]
+    fn checkout_git_branch_runs_git_checkout() -> Result<()> {
+        let picker = select_first(&["master", "feat", "feature/login"], "login");
+        assert_eq!(
+            checkout_git_branch(picker).run()?.to_string(),
+            "BUFFER='git checkout feature/login'\nCURSOR=26\nzle accept-line\n"
+        );
         Ok(())
     }
 
-    fn name(&self) -> &'static str {
-        "cdr"
+    #[test]
+    fn templates_quote_the_selected_values() -> Result<()> {
+        let picker = select_first(&["README.md", "my notes.txt"], "notes");
+        assert_eq!(
+            git_add(picker).run()?.to_string(),
+            "BUFFER='git add -- '\\''my notes.txt'\\'''\nCURSOR=25\nzle accept-line\n"
+        );
+        Ok(())
     }
-}
-/// Kill process widget
-pub struct Kill<S: Source, F: Selector, A: Action> {
-    source: S,
-    selector: F,
-    action: A,
-}
 
-impl<S: Source, F: Selector, A: Action> Kill<S, F, A> {
-    /// Create a new Kill widget
-    pub fn new(source: S, selector: F, action: A) -> Self {
-        Self {
-            source,
-            selector,
-            action,
-        }
+    #[test]
+    fn cd_ghq_repository_changes_directory() -> Result<()> {
+        let picker = select_first(&["/src/github.com/a/b", "/src/github.com/c/d"], "c/d");
+        assert_eq!(
+            cd_ghq_repository(picker).run()?.to_string(),
+            "builtin cd -- /src/github.com/c/d\nzle reset-prompt\n"
+        );
+        Ok(())
     }
-}
-
-impl<S: Source, F: Selector, A: Action> Widget for Kill<S, F, A> {
-    fn run(&self) -> Result<()> {
-        let data = self.source.get_data()?;
-        let selected = self.selector.select(&data, None)?;
 
-        // Extract the first field (PID) from the selected line
-        let pid = selected.split_whitespace().next().unwrap_or("");
-
-        // Execute kill command
-        self.action.perform(&format!("kill {}", pid))?;
+    #[test]
+    fn insert_filename_quotes_paths() -> Result<()> {
+        let picker = select_first(&["src/main.rs", "my notes.txt"], "notes");
+        assert_eq!(
+            insert_filename(picker).run()?.to_string(),
+            "LBUFFER+=''\\''my notes.txt'\\'''\n"
+        );
+        Ok(())
+    }
 
+    #[test]
+    fn insert_filename_quotes_paths_for_alternate_actions() -> Result<()> {
+        let source = vec![Candidate::new("my notes.txt"), Candidate::new("a.rs")];
+        let keys = [(
+            "ctrl-o".to_string(),
+            KeyAction::new(Box::new(Put::new(false))),
+        )];
+        let picker = Picker::new(source.clone(), AcceptWith("ctrl-o")).keys(keys);
+        assert_eq!(
+            insert_filename(picker).run()?.to_string(),
+            "BUFFER=''\\''my notes.txt'\\''\na.rs'\nCURSOR=19\n"
+        );
+
+        let keys = [(
+            "ctrl-e".to_string(),
+            KeyAction::new(Box::new(Execute)).template("vim {}"),
+        )];
+        let picker = Picker::new(source, AcceptWith("ctrl-e")).keys(keys);
+        assert_eq!(
+            insert_filename(picker).run()?.to_string(),
+            "BUFFER='vim '\\''my notes.txt'\\'' a.rs'\nCURSOR=23\nzle accept-line\n"
+        );
         Ok(())
     }
 
-    fn name(&self) -> &'static str {
-        "kill"
+    #[test]
+    fn fails_without_a_match() {
+        let picker = select_first(&["master", "feat"], "xyz");
+        assert!(matches!(
+            checkout_git_branch(picker).run(),
+            Err(error::AnyframeError::NoMatch)
+        ));
     }
 }
-// Similar implementations for other widgets to be added
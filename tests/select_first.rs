@@ -0,0 +1,50 @@
+//! Runs widgets through the binary with `--select-first`, which needs no terminal
+
+#![cfg(unix)]
+
+use std::process::{Command, Output};
+
+const CONFIG: &str = r#"
+[widgets.checkout]
+source = "command:printf 'master\nfeat\nfeature/login\n'"
+action = "execute"
+template = "git checkout {}"
+"#;
+
+/// Write the configuration to a file of its own and run the binary with it
+fn run(name: &str, args: &[&str]) -> std::io::Result<Output> {
+    let config = std::env::temp_dir().join(format!(
+        "anyframe-rs-test-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    std::fs::write(&config, CONFIG)?;
+    let output = Command::new(env!("CARGO_BIN_EXE_anyframe-rs"))
+        .arg("--config")
+        .arg(&config)
+        .args(args)
+        .env_remove("TMUX")
+        .env("XDG_CACHE_HOME", std::env::temp_dir())
+        .output();
+    std::fs::remove_file(&config)?;
+    output
+}
+
+#[test]
+fn runs_the_best_match() -> std::io::Result<()> {
+    let output = run("match", &["--select-first", "login", "run", "checkout"])?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "BUFFER='git checkout feature/login'\nCURSOR=26\nzle accept-line\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn exits_with_1_without_a_match() -> std::io::Result<()> {
+    let output = run("no-match", &["--select-first", "xyz", "run", "checkout"])?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    Ok(())
+}